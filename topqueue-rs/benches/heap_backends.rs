@@ -0,0 +1,69 @@
+//! Compares `TopQueue` backed by each of the heaps in `topqueue::heap`.
+//!
+//! Ascending input is the adversarial case: every item evicts the current
+//! minimum and then sifts all the way to the bottom of the heap. Descending
+//! input is the opposite, with every item after the first few rejected.
+//!
+//! Run with `cargo bench --bench heap_backends`.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use topqueue::heap::{DaryHeap, Heap, MinMaxHeap};
+use topqueue::topqueue_final::TopQueue;
+use topqueue::util::rands;
+
+const ROUNDS: u32 = 10;
+
+/// Returns the fastest of `ROUNDS` runs of `f`.
+fn fastest(mut f: impl FnMut()) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn time<H: Heap<i32>>(capacity: usize, nums: &[i32]) -> Duration {
+    fastest(|| {
+        let mut q = TopQueue::new(capacity).with_heap::<H>();
+        for &n in nums {
+            q.push(n);
+        }
+        black_box(q);
+    })
+}
+
+fn bench(name: &str, nums: &[i32]) {
+    for capacity in [100, 10_000] {
+        println!("{name} input, capacity {capacity}:");
+        let results = [
+            (
+                "BinaryHeap",
+                time::<BinaryHeap<Reverse<i32>>>(capacity, nums),
+            ),
+            ("DaryHeap<2>", time::<DaryHeap<i32, 2>>(capacity, nums)),
+            ("DaryHeap<4>", time::<DaryHeap<i32, 4>>(capacity, nums)),
+            ("DaryHeap<8>", time::<DaryHeap<i32, 8>>(capacity, nums)),
+            ("MinMaxHeap", time::<MinMaxHeap<i32>>(capacity, nums)),
+        ];
+        for (heap, elapsed) in results {
+            println!("  {heap:<12} {elapsed:>10.3?}");
+        }
+    }
+}
+
+fn main() {
+    let random: Vec<i32> = rands().take(1_000_000).collect();
+    let ascending: Vec<i32> = (0..1_000_000).collect();
+    let descending: Vec<i32> = ascending.iter().rev().copied().collect();
+
+    bench("random", &random);
+    bench("ascending", &ascending);
+    bench("descending", &descending);
+}
@@ -0,0 +1,46 @@
+//! Compares `TopQueue::push_batch` against a loop of `push` calls.
+//!
+//! Run with `cargo bench --bench push_batch`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use topqueue::topqueue_final::TopQueue;
+use topqueue::util::rands;
+
+const ROUNDS: u32 = 20;
+
+/// Returns the fastest of `ROUNDS` runs of `f`.
+fn fastest(mut f: impl FnMut()) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn main() {
+    let nums: Vec<i32> = rands().take(1_000_000).collect();
+
+    for capacity in [10, 100, 1000, 10_000] {
+        let push = fastest(|| {
+            let mut q = TopQueue::new(capacity);
+            for &n in &nums {
+                q.push(n);
+            }
+            black_box(q);
+        });
+        let batch = fastest(|| {
+            let mut q = TopQueue::new(capacity);
+            q.push_batch(&nums);
+            black_box(q);
+        });
+        println!(
+            "capacity {capacity:>6}: push {push:>10.3?}  push_batch {batch:>10.3?}  ({:.1}x)",
+            push.as_secs_f64() / batch.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! Times each `topqueue::adaptive::Strategy` on random input, across slice
+//! sizes and values of `k`, to calibrate `Strategy::choose`.
+//!
+//! Slices here are small enough to copy, so the heap is never chosen, and is
+//! timed to show what bounding memory costs. When the heap is chosen isn't
+//! calibrated here: that's a limit on memory, not a measured crossover.
+//!
+//! Each line marks the fastest strategy with `*`, and the one `top_k_auto`
+//! would choose with `<`.
+//!
+//! Run with `cargo bench --bench top_k_auto`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use topqueue::adaptive::{top_k_with, Strategy};
+use topqueue::util::rands;
+
+/// Beyond this `k`, insertion takes minutes and isn't timed.
+const INSERTION_TIMING_LIMIT: usize = 1024;
+
+/// Roughly how many items to process per timing, so that small slices are
+/// timed over many runs.
+const WORK: usize = 4_000_000;
+
+/// Returns the fastest of several runs of `f`, each repeated `reps` times.
+fn fastest(reps: usize, mut f: impl FnMut()) -> Duration {
+    (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..reps {
+                f();
+            }
+            start.elapsed() / u32::try_from(reps).unwrap()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn main() {
+    for n in [100, 1000, 10_000, 100_000, 1_000_000] {
+        let nums: Vec<i32> = rands().take(n).collect();
+        let reps = (WORK / n).max(1);
+        println!("n = {n}:");
+        let ks = [
+            1,
+            8,
+            32,
+            64,
+            128,
+            n / 64,
+            n / 4,
+            n / 2,
+            n - n / 4,
+            n - n / 8,
+            n - n / 16,
+            n,
+        ];
+        for k in ks {
+            if k == 0 || k > n {
+                continue;
+            }
+            let times: Vec<_> = Strategy::ALL
+                .into_iter()
+                .filter(|&s| s != Strategy::Insertion || k <= INSERTION_TIMING_LIMIT)
+                .map(|strategy| {
+                    let elapsed = fastest(reps, || {
+                        black_box(top_k_with(black_box(&nums), k, strategy));
+                    });
+                    (strategy, elapsed)
+                })
+                .collect();
+            let best = times.iter().min_by_key(|t| t.1).unwrap().0;
+            let chosen = Strategy::choose::<i32>(n, k);
+            print!("  k = {k:>7}:");
+            for (strategy, elapsed) in times {
+                let best = if strategy == best { '*' } else { ' ' };
+                let chosen = if strategy == chosen { '<' } else { ' ' };
+                print!(
+                    "  {:>9} {elapsed:>10.3?}{best}{chosen}",
+                    format!("{strategy:?}")
+                );
+            }
+            println!();
+        }
+    }
+}
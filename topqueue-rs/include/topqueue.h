@@ -0,0 +1,56 @@
+/*
+ * C bindings for the Rust `topqueue` crate.
+ *
+ * A TopQueue retains the largest `capacity` items pushed into it. Handles are
+ * opaque; create them with topqueue_*_new() and release them with
+ * topqueue_*_free(), which nulls out the caller's handle. Every function
+ * accepts a NULL handle.
+ */
+
+#ifndef TOPQUEUE_H
+#define TOPQUEUE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Returned by the drain_sorted functions on failure. */
+#define TOPQUEUE_ERROR ((size_t)-1)
+
+typedef struct TopQueueI64 TopQueueI64;
+typedef struct TopQueueF64 TopQueueF64;
+
+/* Returns a new, empty queue. */
+TopQueueI64 *topqueue_i64_new(size_t capacity);
+
+/* Pushes `item` into the queue. Returns 0, or -1 if `queue` is NULL. */
+int32_t topqueue_i64_push(TopQueueI64 *queue, int64_t item);
+
+/* Returns the number of items in the queue, or 0 if `queue` is NULL. */
+size_t topqueue_i64_len(const TopQueueI64 *queue);
+
+/*
+ * Empties the queue into `out`, smallest first, and returns the number of
+ * items written. If `queue` or `out` is NULL, or `out_len` is less than the
+ * queue's length, the queue is untouched and TOPQUEUE_ERROR is returned.
+ */
+size_t topqueue_i64_drain_sorted(TopQueueI64 *queue, int64_t *out, size_t out_len);
+
+/* Frees `*queue` and sets it to NULL. Does nothing if either is NULL. */
+void topqueue_i64_free(TopQueueI64 **queue);
+
+/* As above, for doubles. Values are ordered as by IEEE 754 totalOrder. */
+TopQueueF64 *topqueue_f64_new(size_t capacity);
+int32_t topqueue_f64_push(TopQueueF64 *queue, double item);
+size_t topqueue_f64_len(const TopQueueF64 *queue);
+size_t topqueue_f64_drain_sorted(TopQueueF64 *queue, double *out, size_t out_len);
+void topqueue_f64_free(TopQueueF64 **queue);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TOPQUEUE_H */
@@ -0,0 +1,280 @@
+//! Picks the fastest way to find the top `k` of a slice, by its size.
+//!
+//! No one method wins everywhere. For the top few dozen of a large slice,
+//! keeping them in a small sorted array is fastest. Past that, quickselect with
+//! [`select_nth_unstable`] wins until `k` is nearly the whole slice, and then
+//! sorting everything, as [`get_top`] does. Both copy the slice, though, so
+//! for slices too large to copy a [`TopQueue`] keeps only the top `k`.
+//! [`top_k_auto`] chooses between them by [`Strategy::choose`], and
+//! [`top_k_with`] runs any one of them.
+//!
+//! Every strategy returns the same items in the same order, even among items
+//! that compare equal without being identical, so the choice only affects
+//! speed:
+//!
+//! ```
+//! # use topqueue::adaptive::{top_k_auto, top_k_with, Strategy};
+//! let nums = [5, 1, 9, 3, 7, 9, 2];
+//! assert_eq!(vec![9, 9, 7], top_k_auto(&nums, 3));
+//! for strategy in Strategy::ALL {
+//!     assert_eq!(vec![9, 9, 7], top_k_with(&nums, 3, strategy));
+//! }
+//! ```
+//!
+//! The thresholds in [`Strategy::choose`] between insertion, quickselect and
+//! sorting come from the `top_k_auto` benchmark, which times each strategy on
+//! random input; run it with `cargo bench --bench top_k_auto` to check them
+//! on other hardware. The switch to a heap is a limit on memory instead, set
+//! by how large a copy is too large.
+//!
+//! [`get_top`]: crate::util::get_top
+//! [`select_nth_unstable`]: slice::select_nth_unstable
+
+use std::cmp::{Ordering, Reverse};
+
+use crate::topqueue_final::TopQueue;
+
+/// Up to this `k`, and while the slice is at least `INSERTION_RATIO` times
+/// `k`, a sorted array beats quickselect.
+const INSERTION_MAX_K: usize = 64;
+const INSERTION_RATIO: usize = 64;
+
+/// Once all but `1 / SORT_GAP` of the slice is wanted, sorting beats
+/// quickselect, whose partitioning then leaves little unsorted.
+const SORT_GAP: usize = 16;
+
+/// The most memory quickselect and sorting may take to copy the slice.
+/// Beyond it, a heap's *O*(*k*) memory is worth the time it takes.
+///
+/// Unlike the thresholds above, this isn't calibrated by the benchmark. It's
+/// a policy on memory: the heap is slower at every size the benchmark times,
+/// and is chosen only to avoid a copy this large.
+const COPY_MAX_BYTES: usize = 256 << 20;
+
+/// A way of finding the top `k` items of a slice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Strategy {
+    /// Keeps the top items seen in a sorted array, inserting each item that
+    /// beats the smallest. *O*(*nk*) at worst, but with no heap overhead.
+    Insertion,
+    /// Keeps the top items seen in a [`TopQueue`]. *O*(*n* log *k*), in
+    /// *O*(*k*) memory, but slower than quickselect.
+    Heap,
+    /// Partitions a copy of the slice around its `k`th largest item, then
+    /// collects and sorts the top `k`. *O*(*n* + *k* log *k*), in *O*(*n*)
+    /// memory.
+    Select,
+    /// Sorts a copy of the whole slice. *O*(*n* log *n*).
+    Sort,
+}
+
+impl Strategy {
+    /// Every strategy.
+    pub const ALL: [Strategy; 4] = [
+        Strategy::Insertion,
+        Strategy::Heap,
+        Strategy::Select,
+        Strategy::Sort,
+    ];
+
+    /// Returns the strategy expected to be fastest for the top `k` of `n`
+    /// items of type `T`.
+    ///
+    /// ```
+    /// # use topqueue::adaptive::Strategy;
+    /// assert_eq!(Strategy::Insertion, Strategy::choose::<i32>(1_000_000, 10));
+    /// assert_eq!(Strategy::Select, Strategy::choose::<i32>(1_000_000, 1000));
+    /// assert_eq!(Strategy::Sort, Strategy::choose::<i32>(1_000_000, 950_000));
+    /// // A copy of a billion of them would take 4 GB.
+    /// assert_eq!(Strategy::Heap, Strategy::choose::<i32>(1 << 30, 1000));
+    /// ```
+    #[must_use]
+    pub fn choose<T>(n: usize, k: usize) -> Self {
+        let k = k.min(n);
+        if k <= INSERTION_MAX_K && k.saturating_mul(INSERTION_RATIO) <= n {
+            Strategy::Insertion
+        } else if n.saturating_mul(size_of::<T>()) > COPY_MAX_BYTES {
+            Strategy::Heap
+        } else if n - k <= n / SORT_GAP {
+            Strategy::Sort
+        } else {
+            Strategy::Select
+        }
+    }
+}
+
+/// Returns the `k` largest items of `items`, largest first, by whichever
+/// [`Strategy`] should be fastest for their sizes.
+///
+/// Items that compare equal are returned in the order they appear in
+/// `items`, and when only some of them fit in the top `k`, the earliest are
+/// kept. That holds for every strategy, so the result doesn't depend on which
+/// is chosen.
+#[must_use]
+pub fn top_k_auto<T: Ord + Clone>(items: &[T], k: usize) -> Vec<T> {
+    top_k_with(items, k, Strategy::choose::<T>(items.len(), k))
+}
+
+/// Returns the `k` largest items of `items`, largest first, by `strategy`.
+///
+/// Equal items are ordered and chosen as by [`top_k_auto`].
+#[must_use]
+pub fn top_k_with<T: Ord + Clone>(items: &[T], k: usize, strategy: Strategy) -> Vec<T> {
+    let k = k.min(items.len());
+    if k == 0 {
+        return Vec::new();
+    }
+    match strategy {
+        Strategy::Insertion => insertion(items, k),
+        Strategy::Heap => {
+            // Among equal items, the earliest rank highest. Only the items
+            // kept are cloned.
+            let indexed = items.iter().enumerate().map(|(i, item)| (item, Reverse(i)));
+            let top = TopQueue::from_iter(k, indexed).into_vec();
+            top.into_iter()
+                .rev()
+                .map(|(item, _)| item.clone())
+                .collect()
+        }
+        Strategy::Select => select(items, k),
+        Strategy::Sort => {
+            let mut top = items.to_vec();
+            top.sort_by(|a, b| b.cmp(a));
+            top.truncate(k);
+            top
+        }
+    }
+}
+
+/// Finds the `k`th largest item by quickselect, then takes every larger item
+/// and as many of those equal to it as fit from `items`, in their order.
+fn select<T: Ord + Clone>(items: &[T], k: usize) -> Vec<T> {
+    let mut top = items.to_vec();
+    let (larger, kth, _) = top.select_nth_unstable_by(k - 1, |a, b| b.cmp(a));
+    let mut ties = k - larger.iter().filter(|item| *item > kth).count();
+    let kth = kth.clone();
+    top.clear();
+    top.extend(
+        items
+            .iter()
+            .filter(|item| match (*item).cmp(&kth) {
+                Ordering::Greater => true,
+                Ordering::Equal if ties > 0 => {
+                    ties -= 1;
+                    true
+                }
+                _ => false,
+            })
+            .cloned(),
+    );
+    top.sort_by(|a, b| b.cmp(a));
+    top
+}
+
+/// Keeps the `k` largest items seen in an array sorted largest first. A new
+/// item goes after those equal to it, and doesn't displace them.
+fn insertion<T: Ord + Clone>(items: &[T], k: usize) -> Vec<T> {
+    let mut top: Vec<T> = Vec::with_capacity(k + 1);
+    for item in items {
+        if top.len() == k && top.last().is_some_and(|min| item <= min) {
+            continue;
+        }
+        let at = top.partition_point(|x| x >= item);
+        top.insert(at, item.clone());
+        top.truncate(k);
+    }
+    top
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::{top_k_auto, top_k_with, Strategy};
+    use crate::util::{get_top, rands};
+
+    #[test]
+    fn strategies_match_get_top() {
+        let random: Vec<i32> = rands().take(2000).collect();
+        let few: Vec<i32> = rands::<u8>().take(2000).map(|n| i32::from(n % 4)).collect();
+        let ascending: Vec<i32> = (0..2000).collect();
+        let descending: Vec<i32> = (0..2000).rev().collect();
+        for nums in [&random, &few, &ascending, &descending] {
+            for k in [0, 1, 2, 16, 17, 100, 1000, 1999, 2000, 5000] {
+                let expected = get_top(nums, k);
+                for strategy in Strategy::ALL {
+                    assert_eq!(expected, top_k_with(nums, k, strategy), "{strategy:?}");
+                }
+                assert_eq!(expected, top_k_auto(nums, k));
+            }
+        }
+        assert!(top_k_auto::<i32>(&[], 3).is_empty());
+    }
+
+    /// Ordered by `priority` alone, so jobs can compare equal but differ.
+    #[derive(Debug, Clone)]
+    struct Job {
+        priority: u8,
+        id: usize,
+    }
+
+    impl PartialEq for Job {
+        fn eq(&self, other: &Self) -> bool {
+            self.priority == other.priority
+        }
+    }
+
+    impl Eq for Job {}
+
+    impl PartialOrd for Job {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Job {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.priority.cmp(&other.priority)
+        }
+    }
+
+    #[test]
+    fn strategies_keep_equal_items_in_order() {
+        let jobs: Vec<Job> = rands::<u8>()
+            .take(2000)
+            .enumerate()
+            .map(|(id, n)| Job {
+                priority: n % 8,
+                id,
+            })
+            .collect();
+        for k in [1, 10, 100, 250, 1000, 1999, 2000] {
+            let mut expected = jobs.clone();
+            expected.sort_by(|a, b| b.cmp(a));
+            expected.truncate(k);
+            let ids = |top: Vec<Job>| top.into_iter().map(|job| job.id).collect::<Vec<_>>();
+            for strategy in Strategy::ALL {
+                assert_eq!(
+                    ids(expected.clone()),
+                    ids(top_k_with(&jobs, k, strategy)),
+                    "{strategy:?}, k {k}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn choose_follows_sizes() {
+        assert_eq!(Strategy::Insertion, Strategy::choose::<i32>(0, 10));
+        assert_eq!(Strategy::Insertion, Strategy::choose::<i32>(1000, 15));
+        assert_eq!(Strategy::Insertion, Strategy::choose::<u8>(usize::MAX, 64));
+        assert_eq!(Strategy::Select, Strategy::choose::<i32>(1000, 16));
+        assert_eq!(Strategy::Select, Strategy::choose::<i32>(1_000_000, 65));
+        assert_eq!(Strategy::Select, Strategy::choose::<i32>(1000, 937));
+        assert_eq!(Strategy::Sort, Strategy::choose::<i32>(1000, 938));
+        assert_eq!(Strategy::Sort, Strategy::choose::<i32>(10, usize::MAX));
+        assert_eq!(Strategy::Select, Strategy::choose::<u8>(1 << 28, 100));
+        assert_eq!(Strategy::Heap, Strategy::choose::<u16>(1 << 28, 100));
+        assert_eq!(Strategy::Heap, Strategy::choose::<[u8; 1 << 20]>(257, 200));
+    }
+}
@@ -0,0 +1,155 @@
+//! Replays a trace written by `topqueue::trace::Recorder`, checking it.
+//!
+//! ```text
+//! topqueue-replay TRACE [--bisect]
+//! ```
+//!
+//! Without `--bisect`, the queue is rebuilt from the trace, and each snapshot
+//! in it checked against the rebuilt queue. With it, the queue is compared
+//! against `topqueue::trace::sorted_oracle` instead, and the first operation
+//! after which they disagree is reported.
+//!
+//! Either way, the replayed queue's items are printed largest first. The
+//! exit status is 0 if everything matched, 1 if something didn't, and 2 if
+//! the trace couldn't be read.
+
+use std::fmt::Debug;
+use std::fs;
+use std::process;
+
+use topqueue::topqueue_final::TopQueue;
+use topqueue::trace::{item_type, sorted_oracle, Trace, TraceItem};
+use topqueue::util::OrdF64;
+
+const USAGE: &str = "usage: topqueue-replay TRACE [--bisect]";
+
+struct Config {
+    path: String,
+    bisect: bool,
+}
+
+/// What replaying found, if not an error reading the trace.
+enum Outcome {
+    Matched(String),
+    Diverged(String),
+}
+
+fn main() {
+    let config = parse_args(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("topqueue-replay: {err}\n{USAGE}");
+        process::exit(2);
+    });
+    match run(&config) {
+        Ok(Outcome::Matched(report)) => println!("{report}"),
+        Ok(Outcome::Diverged(report)) => {
+            println!("{report}");
+            process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("topqueue-replay: {}: {err}", config.path);
+            process::exit(2);
+        }
+    }
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Config, String> {
+    let mut path = None;
+    let mut bisect = false;
+    for arg in args {
+        match arg.as_str() {
+            "--bisect" => bisect = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown argument {arg}")),
+            _ if path.is_some() => return Err(format!("unexpected argument {arg}")),
+            _ => path = Some(arg),
+        }
+    }
+    Ok(Config {
+        path: path.ok_or("a trace file is required")?,
+        bisect,
+    })
+}
+
+fn run(config: &Config) -> Result<Outcome, String> {
+    let bytes = fs::read(&config.path).map_err(|err| err.to_string())?;
+    let check = match item_type(&bytes).ok_or("not a trace")? {
+        "i8" => check::<i8>,
+        "i16" => check::<i16>,
+        "i32" => check::<i32>,
+        "i64" => check::<i64>,
+        "u8" => check::<u8>,
+        "u16" => check::<u16>,
+        "u32" => check::<u32>,
+        "u64" => check::<u64>,
+        "f64" => check::<OrdF64>,
+        name => return Err(format!("can't replay items of type {name}")),
+    };
+    check(&bytes, config.bisect)
+}
+
+fn check<T: TraceItem + Debug>(bytes: &[u8], bisect: bool) -> Result<Outcome, String> {
+    let trace = Trace::<T>::read_from(bytes).map_err(|err| err.to_string())?;
+    let ops = trace.ops.len();
+    if bisect {
+        let top = describe(&trace.replay_prefix(ops));
+        return Ok(match trace.bisect(sorted_oracle) {
+            None => Outcome::Matched(format!("{ops} ops agree with the oracle: {top}")),
+            Some(i) => Outcome::Diverged(format!(
+                "first diverges from the oracle at op {i}, {:?}: {top}",
+                trace.ops[i]
+            )),
+        });
+    }
+    Ok(match trace.replay() {
+        Ok(queue) => Outcome::Matched(format!("{ops} ops replayed: {}", describe(&queue))),
+        Err(mismatch) => Outcome::Diverged(format!(
+            "snapshot at op {} differs: recorded {:?}, replayed {:?}",
+            mismatch.op, mismatch.expected, mismatch.actual
+        )),
+    })
+}
+
+/// Lists a queue's items, largest first.
+fn describe<T: TraceItem + Debug>(queue: &TopQueue<T>) -> String {
+    format!("{:?}", queue.page(0, queue.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, parse_args, Outcome};
+    use topqueue::trace::record;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn parses_args() {
+        let config = parse_args(args(&["t.trace", "--bisect"])).unwrap();
+        assert_eq!(("t.trace", true), (config.path.as_str(), config.bisect));
+        assert!(parse_args(args(&[])).is_err());
+        assert!(parse_args(args(&["a", "b"])).is_err());
+        assert!(parse_args(args(&["a", "--fast"])).is_err());
+    }
+
+    #[test]
+    fn checks_snapshots_and_bisects() {
+        let mut q = record::<i64>(2);
+        q.push(3);
+        q.push(1);
+        q.record_snapshot();
+        q.push(2);
+        let bytes = q.into_observer().finish().unwrap();
+        let Ok(Outcome::Matched(report)) = check::<i64>(&bytes, false) else {
+            panic!("replay didn't match");
+        };
+        assert_eq!("4 ops replayed: [3, 2]", report);
+        let Ok(Outcome::Matched(report)) = check::<i64>(&bytes, true) else {
+            panic!("bisect didn't match");
+        };
+        assert_eq!("4 ops agree with the oracle: [3, 2]", report);
+        assert!(check::<i32>(&bytes, false).is_err());
+    }
+}
@@ -0,0 +1,428 @@
+//! A local server that owns named `TopQueue`s of `i64`s, for several
+//! processes to feed the same leaderboards.
+//!
+//! ```text
+//! topqueue-server (--tcp ADDR | --unix PATH) [--checkpoint-dir DIR] [--restore FILE]...
+//! ```
+//!
+//! The server listens on a loopback TCP address or a Unix socket, and serves
+//! each client on its own thread. Clients send one command per line, and get
+//! one line back: `OK`, followed by any results, or `ERR` and a message.
+//!
+//! | Command               | Effect                                           |
+//! |-----------------------|--------------------------------------------------|
+//! | `CREATE name cap`     | Creates a queue retaining the `cap` largest.     |
+//! | `PUSH name value`     | Pushes `value` into a queue.                     |
+//! | `TOP name`            | Replies with a queue's items, largest first.     |
+//! | `SNAPSHOT name file`  | Checkpoints a queue to `file` in the checkpoint directory. |
+//! | `MERGE dst src`       | Pushes every item of `src` into `dst`.           |
+//!
+//! Checkpoints are written only inside `--checkpoint-dir`, so clients can't
+//! write elsewhere, and are loaded back with `--restore` when the server
+//! starts. Once it's listening, the server prints `listening on` and its
+//! address.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use topqueue::topqueue_final::TopQueue;
+
+/// The largest capacity a client may ask for.
+const MAX_CAPACITY: usize = 1_000_000;
+
+/// The first word of every checkpoint file.
+const CHECKPOINT_MAGIC: &str = "topqueue-checkpoint";
+
+const USAGE: &str =
+    "usage: topqueue-server (--tcp ADDR | --unix PATH) [--checkpoint-dir DIR] [--restore FILE]...";
+
+/// Where the server listens.
+enum Listen {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+struct Config {
+    listen: Listen,
+    checkpoint_dir: Option<PathBuf>,
+    restore: Vec<PathBuf>,
+}
+
+/// The queues, shared by every client.
+struct Server {
+    queues: Mutex<HashMap<String, TopQueue<i64>>>,
+    checkpoint_dir: Option<PathBuf>,
+    /// Numbers snapshots in the order their contents were rendered.
+    snapshots: AtomicUsize,
+    /// The number of the newest snapshot renamed into each checkpoint file.
+    written: Mutex<HashMap<PathBuf, usize>>,
+}
+
+fn main() {
+    let config = parse_args(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("topqueue-server: {err}\n{USAGE}");
+        process::exit(2);
+    });
+    if let Err(err) = run(config) {
+        eprintln!("topqueue-server: {err}");
+        process::exit(1);
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Config, String> {
+    let mut listen = None;
+    let mut checkpoint_dir = None;
+    let mut restore = Vec::new();
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or(format!("{flag} needs a value"));
+        match flag.as_str() {
+            "--tcp" => {
+                let addr: SocketAddr = value()?.parse().map_err(|e| format!("--tcp: {e}"))?;
+                if !addr.ip().is_loopback() {
+                    return Err(format!("--tcp: {addr} is not a loopback address"));
+                }
+                listen = Some(Listen::Tcp(addr));
+            }
+            #[cfg(unix)]
+            "--unix" => listen = Some(Listen::Unix(value()?.into())),
+            "--checkpoint-dir" => checkpoint_dir = Some(value()?.into()),
+            "--restore" => restore.push(value()?.into()),
+            _ => return Err(format!("unknown argument {flag}")),
+        }
+    }
+    Ok(Config {
+        listen: listen.ok_or("one of --tcp or --unix is required")?,
+        checkpoint_dir,
+        restore,
+    })
+}
+
+fn run(config: Config) -> io::Result<()> {
+    let mut queues = HashMap::new();
+    for path in &config.restore {
+        let (name, queue) = read_checkpoint(path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))?;
+        queues.insert(name, queue);
+    }
+    let server = Arc::new(Server {
+        queues: Mutex::new(queues),
+        checkpoint_dir: config.checkpoint_dir,
+        snapshots: AtomicUsize::new(0),
+        written: Mutex::new(HashMap::new()),
+    });
+
+    match config.listen {
+        Listen::Tcp(addr) => {
+            let listener = TcpListener::bind(addr)?;
+            announce(&listener.local_addr()?);
+            accept_clients(&server, listener.incoming(), TcpStream::try_clone);
+        }
+        #[cfg(unix)]
+        Listen::Unix(path) => {
+            remove_stale_socket(&path)?;
+            let listener = UnixListener::bind(&path)?;
+            announce(&path.display());
+            accept_clients(&server, listener.incoming(), UnixStream::try_clone);
+        }
+    }
+    Ok(())
+}
+
+fn announce(addr: &dyn std::fmt::Display) {
+    println!("listening on {addr}");
+    // Whoever started the server may be waiting for this line.
+    let _ = io::stdout().flush();
+}
+
+/// Removes a socket left behind by an earlier server, but nothing else.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+/// Serves each connection on its own thread, for as long as the listener
+/// lasts. A failed accept, as when the process is out of file descriptors,
+/// only costs that one client, never the queues.
+fn accept_clients<S>(
+    server: &Arc<Server>,
+    incoming: impl Iterator<Item = io::Result<S>>,
+    try_clone: fn(&S) -> io::Result<S>,
+) where
+    S: io::Read + Write + Send + 'static,
+{
+    for stream in incoming {
+        match stream.and_then(|stream| Ok((try_clone(&stream)?, stream))) {
+            Ok((reader, writer)) => spawn_client(server, reader, writer),
+            Err(err) => {
+                eprintln!("topqueue-server: couldn't accept a client: {err}");
+                // Give whatever ran out a moment to come back.
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+}
+
+fn spawn_client<R, W>(server: &Arc<Server>, reader: R, writer: W)
+where
+    R: io::Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    let server = Arc::clone(server);
+    thread::spawn(move || {
+        // A client that goes away mid-conversation is no concern of ours.
+        let _ = serve(&server, BufReader::new(reader), writer);
+    });
+}
+
+fn serve(server: &Server, reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+    for line in reader.lines() {
+        let reply = match server.handle(&line?) {
+            Ok(reply) => reply,
+            Err(err) => format!("ERR {err}"),
+        };
+        writeln!(writer, "{reply}")?;
+    }
+    Ok(())
+}
+
+impl Server {
+    /// Runs one command, returning the reply line.
+    fn handle(&self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let mut queues = self.queues.lock().unwrap_or_else(PoisonError::into_inner);
+        match words.as_slice() {
+            ["CREATE", name, capacity] => {
+                let capacity: usize = capacity
+                    .parse()
+                    .map_err(|_| format!("bad capacity {capacity}"))?;
+                if capacity > MAX_CAPACITY {
+                    return Err(format!("capacity is at most {MAX_CAPACITY}"));
+                }
+                if queues.contains_key(*name) {
+                    return Err(format!("{name} already exists"));
+                }
+                queues.insert((*name).to_string(), TopQueue::new(capacity));
+                Ok("OK".into())
+            }
+            ["PUSH", name, value] => {
+                let value: i64 = value.parse().map_err(|_| format!("bad value {value}"))?;
+                get_mut(&mut queues, name)?.push(value);
+                Ok("OK".into())
+            }
+            ["TOP", name] => {
+                let queue = get_mut(&mut queues, name)?;
+                let mut reply = String::from("OK");
+                for item in queue.page(0, queue.len()) {
+                    let _ = write!(reply, " {item}");
+                }
+                Ok(reply)
+            }
+            ["SNAPSHOT", name, file] => {
+                let path = self.checkpoint_path(file)?;
+                let contents = checkpoint(name, get_mut(&mut queues, name)?);
+                let number = self.snapshots.fetch_add(1, Ordering::Relaxed);
+                // Other clients needn't wait on the disk.
+                drop(queues);
+                self.write_checkpoint(&path, &contents, number)
+                    .map_err(|err| err.to_string())?;
+                Ok("OK".into())
+            }
+            ["MERGE", dst, src] => {
+                if dst == src {
+                    return Err("can't merge a queue into itself".into());
+                }
+                let src = get_mut(&mut queues, src)?;
+                let items: Vec<i64> = src.page(0, src.len()).into_iter().copied().collect();
+                let dst = get_mut(&mut queues, dst)?;
+                for item in items {
+                    dst.push(item);
+                }
+                Ok("OK".into())
+            }
+            [command, ..] => Err(format!("bad command {command}")),
+            [] => Err("empty command".into()),
+        }
+    }
+
+    /// Resolves a checkpoint file name inside the checkpoint directory.
+    fn checkpoint_path(&self, file: &str) -> Result<PathBuf, String> {
+        let dir = self
+            .checkpoint_dir
+            .as_ref()
+            .ok_or("no --checkpoint-dir was given")?;
+        let is_plain_name = Path::new(file).file_name() == Some(file.as_ref());
+        if !is_plain_name {
+            return Err(format!("bad checkpoint file name {file}"));
+        }
+        Ok(dir.join(file))
+    }
+
+    /// Writes snapshot `number` to `path`, unless a later snapshot of the same
+    /// file was written first.
+    ///
+    /// The file is written beside its destination and renamed into place, so a
+    /// crash never leaves a partial checkpoint. Snapshots are numbered while
+    /// the queues are locked, so when two writes of a file race, it ends up
+    /// holding the one asked for last.
+    fn write_checkpoint(&self, path: &Path, contents: &str, number: usize) -> io::Result<()> {
+        let tmp = path.with_extension(format!("{number}.tmp"));
+        fs::write(&tmp, contents)?;
+        let mut written = self.written.lock().unwrap_or_else(PoisonError::into_inner);
+        if written.get(path).is_some_and(|&newest| newest > number) {
+            return fs::remove_file(&tmp);
+        }
+        fs::rename(&tmp, path)?;
+        written.insert(path.to_path_buf(), number);
+        Ok(())
+    }
+}
+
+fn get_mut<'a>(
+    queues: &'a mut HashMap<String, TopQueue<i64>>,
+    name: &str,
+) -> Result<&'a mut TopQueue<i64>, String> {
+    queues
+        .get_mut(name)
+        .ok_or_else(|| format!("no queue {name}"))
+}
+
+/// Renders the checkpoint file of the queue called `name`: a header line
+/// naming the queue and its capacity, then each item on its own line,
+/// smallest first.
+fn checkpoint(name: &str, queue: &TopQueue<i64>) -> String {
+    let mut contents = format!("{CHECKPOINT_MAGIC} {name} {}\n", queue.capacity());
+    for item in queue.page(0, queue.len()).into_iter().rev() {
+        let _ = writeln!(contents, "{item}");
+    }
+    contents
+}
+
+fn read_checkpoint(path: &Path) -> io::Result<(String, TopQueue<i64>)> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let contents = fs::read_to_string(path)?;
+    let mut lines = contents.lines();
+    let header: Vec<&str> = lines.next().unwrap_or("").split_whitespace().collect();
+    let [CHECKPOINT_MAGIC, name, capacity] = header.as_slice() else {
+        return Err(invalid("not a checkpoint file"));
+    };
+    let capacity = capacity
+        .parse()
+        .map_err(|_| invalid("bad capacity in header"))?;
+    let mut queue = TopQueue::new(capacity);
+    for line in lines {
+        queue.push(line.parse().map_err(|_| invalid("bad item"))?);
+    }
+    Ok(((*name).to_string(), queue))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+
+    use super::{parse_args, read_checkpoint, Server};
+
+    fn server() -> Server {
+        Server {
+            queues: Mutex::new(HashMap::new()),
+            checkpoint_dir: Some(std::env::temp_dir()),
+            snapshots: AtomicUsize::new(0),
+            written: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[test]
+    fn commands_update_queues() {
+        let s = server();
+        assert_eq!(Ok("OK".into()), s.handle("CREATE a 2"));
+        assert_eq!(Ok("OK".into()), s.handle("CREATE b 3"));
+        for n in [5, 1, 9] {
+            s.handle(&format!("PUSH a {n}")).unwrap();
+        }
+        s.handle("PUSH b -4").unwrap();
+        assert_eq!(Ok("OK 9 5".into()), s.handle("TOP a"));
+        assert_eq!(Ok("OK".into()), s.handle("MERGE b a"));
+        assert_eq!(Ok("OK 9 5 -4".into()), s.handle("TOP b"));
+        assert_eq!(Ok("OK 9 5".into()), s.handle("TOP a"));
+    }
+
+    #[test]
+    fn bad_commands_are_errors() {
+        let s = server();
+        s.handle("CREATE a 1").unwrap();
+        for line in [
+            "",
+            "FROB a",
+            "CREATE a 1",
+            "CREATE b lots",
+            "CREATE b 1000000000",
+            "PUSH a 1.5",
+            "PUSH nope 1",
+            "TOP",
+            "MERGE a a",
+            "SNAPSHOT a ../escape",
+            "SNAPSHOT a /tmp/abs",
+        ] {
+            assert!(s.handle(line).is_err(), "{line:?} succeeded");
+        }
+    }
+
+    #[test]
+    fn snapshots_restore() {
+        let s = server();
+        s.handle("CREATE scores 2").unwrap();
+        for n in [3, 8, 6] {
+            s.handle(&format!("PUSH scores {n}")).unwrap();
+        }
+        let file = format!("topqueue-server-test-{}.checkpoint", std::process::id());
+        s.handle(&format!("SNAPSHOT scores {file}")).unwrap();
+
+        let path = std::env::temp_dir().join(file);
+        let (name, queue) = read_checkpoint(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!("scores", name);
+        assert_eq!(2, queue.capacity());
+        assert_eq!(vec![6, 8], queue.into_vec());
+    }
+
+    #[test]
+    fn late_snapshot_writes_dont_overwrite_newer_ones() {
+        let s = server();
+        let file = format!("topqueue-server-race-{}.checkpoint", std::process::id());
+        let path = std::env::temp_dir().join(file);
+        // The second snapshot's write finishes before the first's.
+        s.write_checkpoint(&path, "second", 2).unwrap();
+        s.write_checkpoint(&path, "first", 1).unwrap();
+        assert_eq!("second", std::fs::read_to_string(&path).unwrap());
+        assert!(!path.with_extension("1.tmp").exists());
+        s.write_checkpoint(&path, "third", 3).unwrap();
+        assert_eq!("third", std::fs::read_to_string(&path).unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn listen_address_must_be_local() {
+        let args = |line: &str| parse_args(line.split(' ').map(String::from));
+        assert!(args("--tcp 127.0.0.1:0").is_ok());
+        assert!(args("--tcp 0.0.0.0:7000").is_err());
+        assert!(args("--checkpoint-dir /tmp").is_err());
+    }
+}
@@ -0,0 +1,292 @@
+//! A queue that keeps both the largest and the smallest items of a stream.
+//!
+//! ```
+//! # use topqueue::extremes::ExtremesQueue;
+//! let mut q = ExtremesQueue::new(2);
+//! for latency in [120, 45, 300, 80, 15, 95] {
+//!     q.push(latency);
+//! }
+//! assert_eq!(vec![&15, &45], q.bottom());
+//! assert_eq!(vec![&120, &300], q.top());
+//! assert_eq!((Some(&15), Some(&300), 6), (q.min(), q.max(), q.count()));
+//! ```
+
+use crate::heap::{Heap, MinMaxHeap};
+
+/// Retains the `k` largest and the `k` smallest items pushed into it.
+///
+/// Until more than `2k` items have been pushed the two sets may overlap, so
+/// every item is kept once in a single heap. After that the sets are
+/// disjoint and kept in two heaps of `k` items each, so no item is ever
+/// stored twice and `T` need not be `Clone`. Both heaps are min-max heaps,
+/// which makes the exact minimum and maximum available in *O*(1).
+///
+/// With `k` 0 no items are retained, so the minimum and maximum are kept
+/// apart from the heaps instead.
+#[derive(Debug)]
+pub struct ExtremesQueue<T: Ord> {
+    k: usize,
+    count: u64,
+    state: State<T>,
+    /// The smallest item when `k` is 0.
+    min: Option<T>,
+    /// The largest item when `k` is 0, once two items have been pushed.
+    /// Until then the only item is held in `min`.
+    max: Option<T>,
+}
+
+#[derive(Debug)]
+enum State<T: Ord> {
+    /// Every item seen so far, while there are at most `2k` of them.
+    Filling(MinMaxHeap<T>),
+    /// The smallest `k` and largest `k` items.
+    Split {
+        bottom: MinMaxHeap<T>,
+        top: MinMaxHeap<T>,
+    },
+}
+
+impl<T: Ord> ExtremesQueue<T> {
+    /// Creates a queue that tracks the `k` largest and `k` smallest items.
+    #[must_use]
+    pub fn new(k: usize) -> Self {
+        ExtremesQueue {
+            k,
+            count: 0,
+            state: State::Filling(MinMaxHeap::new()),
+            min: None,
+            max: None,
+        }
+    }
+
+    /// Returns how many items are kept at each end.
+    #[must_use]
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Returns the number of items pushed, including those not retained.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the number of distinct items retained, at most `2k`.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        match &self.state {
+            State::Filling(all) => all.len(),
+            State::Split { bottom, top } => bottom.len() + top.len(),
+        }
+    }
+
+    /// Returns true if no items are retained, as when nothing has been
+    /// pushed or `k` is 0.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the smallest item pushed so far.
+    #[must_use]
+    pub fn min(&self) -> Option<&T> {
+        if self.k == 0 {
+            return self.min.as_ref();
+        }
+        match &self.state {
+            State::Filling(all) => all.peek_min(),
+            State::Split { bottom, .. } => bottom.peek_min(),
+        }
+    }
+
+    /// Returns the largest item pushed so far.
+    #[must_use]
+    pub fn max(&self) -> Option<&T> {
+        if self.k == 0 {
+            return self.max.as_ref().or(self.min.as_ref());
+        }
+        match &self.state {
+            State::Filling(all) => all.peek_max(),
+            State::Split { top, .. } => top.peek_max(),
+        }
+    }
+
+    /// Adds `item` to the stream, retaining it if it's among the `k` largest
+    /// or `k` smallest so far.
+    pub fn push(&mut self, item: T) {
+        self.count += 1;
+        if self.k == 0 {
+            self.push_extreme(item);
+            return;
+        }
+        match &mut self.state {
+            State::Filling(all) => {
+                all.push(item);
+                if all.len() > self.k.saturating_mul(2) {
+                    self.split();
+                }
+            }
+            State::Split { bottom, top } => {
+                if top.peek_min().is_some_and(|min| &item >= min) {
+                    top.replace_min(item);
+                } else if bottom.peek_max().is_some_and(|max| &item <= max) {
+                    bottom.pop_max();
+                    bottom.push(item);
+                }
+            }
+        }
+    }
+
+    /// Returns the `k` smallest items, smallest first.
+    #[must_use]
+    pub fn bottom(&self) -> Vec<&T> {
+        let mut items: Vec<&T> = match &self.state {
+            State::Filling(all) => all.items().collect(),
+            State::Split { bottom, .. } => bottom.items().collect(),
+        };
+        items.sort_unstable();
+        items.truncate(self.k);
+        items
+    }
+
+    /// Returns the `k` largest items, smallest first.
+    #[must_use]
+    pub fn top(&self) -> Vec<&T> {
+        let mut items: Vec<&T> = match &self.state {
+            State::Filling(all) => all.items().collect(),
+            State::Split { top, .. } => top.items().collect(),
+        };
+        items.sort_unstable();
+        items.split_off(items.len().saturating_sub(self.k))
+    }
+
+    /// Returns every retained item, smallest first. When more than `2k` items
+    /// were pushed, that's the `k` smallest followed by the `k` largest.
+    #[must_use]
+    pub fn into_vec(self) -> Vec<T> {
+        match self.state {
+            State::Filling(all) => all.into_sorted_vec(),
+            State::Split { bottom, top } => {
+                let mut items = bottom.into_sorted_vec();
+                items.extend(top.into_sorted_vec());
+                items
+            }
+        }
+    }
+
+    /// Keeps `item` if it's a new minimum or maximum, for a queue that
+    /// retains nothing else.
+    fn push_extreme(&mut self, item: T) {
+        let Some(min) = &self.min else {
+            self.min = Some(item);
+            return;
+        };
+        if &item < min {
+            // A lone item is the maximum too, so it moves there.
+            if self.max.is_none() {
+                self.max = self.min.take();
+            }
+            self.min = Some(item);
+        } else if self.max.as_ref().is_none_or(|max| &item > max) {
+            self.max = Some(item);
+        }
+    }
+
+    /// Splits the `2k + 1` items seen so far into the bottom and top `k`,
+    /// dropping the median.
+    fn split(&mut self) {
+        let State::Filling(all) = &mut self.state else {
+            return;
+        };
+        let mut items = std::mem::take(all).into_sorted_vec();
+        let top = items.split_off(self.k + 1);
+        items.truncate(self.k);
+        self.state = State::Split {
+            bottom: MinMaxHeap::from_vec(items),
+            top: MinMaxHeap::from_vec(top),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExtremesQueue;
+    use crate::topqueue_final::TopQueue;
+    use crate::util::rands;
+
+    #[test]
+    fn extremes_match_two_topqueues() {
+        let nums: Vec<i16> = rands().take(5000).collect();
+        for k in [1, 2, 50, 2500, 3000] {
+            let mut q = ExtremesQueue::new(k);
+            for &n in &nums {
+                q.push(n);
+            }
+
+            let top = TopQueue::from_iter(k, nums.iter().copied()).into_vec();
+            let mut bottom: Vec<_> =
+                TopQueue::from_iter(k, nums.iter().map(|&n| std::cmp::Reverse(n)))
+                    .into_vec()
+                    .into_iter()
+                    .map(|r| r.0)
+                    .collect();
+            bottom.reverse();
+
+            assert_eq!(top, q.top().into_iter().copied().collect::<Vec<_>>());
+            assert_eq!(bottom, q.bottom().into_iter().copied().collect::<Vec<_>>());
+            assert_eq!(nums.iter().min(), q.min());
+            assert_eq!(nums.iter().max(), q.max());
+            assert_eq!(5000, q.count());
+            assert!(q.len() <= 2 * k);
+        }
+    }
+
+    #[test]
+    fn extremes_share_storage_until_split() {
+        let mut q = ExtremesQueue::new(3);
+        assert!(q.is_empty());
+        assert_eq!((None, None), (q.min(), q.max()));
+        for n in [5, 1, 9, 7] {
+            q.push(n);
+        }
+        // The four items are each stored once, though both ends include 5 and 7.
+        assert_eq!(4, q.len());
+        assert_eq!(vec![&1, &5, &7], q.bottom());
+        assert_eq!(vec![&5, &7, &9], q.top());
+        assert_eq!(vec![1, 5, 7, 9], q.into_vec());
+    }
+
+    #[test]
+    fn extremes_into_vec_after_split() {
+        let mut q = ExtremesQueue::new(2);
+        for n in 1..=10 {
+            q.push(n);
+        }
+        assert_eq!(4, q.len());
+        assert_eq!(10, q.count());
+        assert_eq!(vec![1, 2, 9, 10], q.into_vec());
+    }
+
+    #[test]
+    fn extremes_with_zero_k_keep_only_min_max_and_count() {
+        let mut q = ExtremesQueue::new(0);
+        assert_eq!((None, None), (q.min(), q.max()));
+        q.push("m");
+        assert_eq!((Some(&"m"), Some(&"m")), (q.min(), q.max()));
+        for s in ["c", "x", "a", "q", "z", "b"] {
+            q.push(s);
+        }
+        assert_eq!((Some(&"a"), Some(&"z")), (q.min(), q.max()));
+        assert_eq!(7, q.count());
+        assert_eq!(0, q.len());
+        assert!(q.is_empty());
+        assert!(q.top().is_empty() && q.bottom().is_empty());
+
+        // A first item that stays the largest.
+        let mut q = ExtremesQueue::new(0);
+        for n in [9, 3, 5] {
+            q.push(n);
+        }
+        assert_eq!((Some(&3), Some(&9)), (q.min(), q.max()));
+    }
+}
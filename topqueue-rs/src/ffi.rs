@@ -0,0 +1,270 @@
+//! C bindings for [`TopQueue`], for `i64` and `f64` items.
+//!
+//! The matching declarations are in `include/topqueue.h`. Each queue is an
+//! opaque handle created by `topqueue_*_new` and released by
+//! `topqueue_*_free`, which takes the address of the handle and nulls it out,
+//! so a second free of the same handle is harmless. Every function accepts a
+//! null handle: `push` and `drain_sorted` report an error, and `len` returns 0.
+//!
+//! ```c
+//! TopQueueI64 *q = topqueue_i64_new(3);
+//! for (int64_t n = 0; n < 10; n++) {
+//!     topqueue_i64_push(q, n);
+//! }
+//! int64_t top[3];
+//! size_t count = topqueue_i64_drain_sorted(q, top, 3); // 3: {7, 8, 9}
+//! topqueue_i64_free(&q);                               // q is now NULL
+//! ```
+//!
+//! `f64` items are ranked by [`f64::total_cmp`], as with [`OrdF64`].
+//!
+//! [`TopQueue`]: crate::topqueue_final::TopQueue
+
+use std::ptr;
+
+use crate::topqueue_final::TopQueue;
+use crate::util::OrdF64;
+
+/// Returned by the `drain_sorted` functions when they fail.
+pub const TOPQUEUE_ERROR: usize = usize::MAX;
+
+/// An opaque `TopQueue` of `i64`s.
+#[derive(Debug)]
+pub struct TopQueueI64(TopQueue<i64>);
+
+/// An opaque `TopQueue` of `f64`s.
+#[derive(Debug)]
+pub struct TopQueueF64(TopQueue<OrdF64>);
+
+/// Creates a queue that retains the largest `capacity` `i64`s pushed into it.
+///
+/// The queue must be released with [`topqueue_i64_free`].
+#[no_mangle]
+pub extern "C" fn topqueue_i64_new(capacity: usize) -> *mut TopQueueI64 {
+    Box::into_raw(Box::new(TopQueueI64(TopQueue::new(capacity))))
+}
+
+/// Pushes `item` into the queue. Returns 0, or -1 if `queue` is null.
+///
+/// # Safety
+///
+/// `queue` must be null or a live handle from [`topqueue_i64_new`].
+#[no_mangle]
+pub unsafe extern "C" fn topqueue_i64_push(queue: *mut TopQueueI64, item: i64) -> i32 {
+    match queue.as_mut() {
+        Some(q) => {
+            q.0.push(item);
+            0
+        }
+        None => -1,
+    }
+}
+
+/// Returns the number of items in the queue, or 0 if `queue` is null.
+///
+/// # Safety
+///
+/// `queue` must be null or a live handle from [`topqueue_i64_new`].
+#[no_mangle]
+pub unsafe extern "C" fn topqueue_i64_len(queue: *const TopQueueI64) -> usize {
+    queue.as_ref().map_or(0, |q| q.0.len())
+}
+
+/// Empties the queue into `out`, smallest first, and returns the number of
+/// items written.
+///
+/// If `queue` or `out` is null, or `out_len` is less than the queue's length,
+/// nothing is written, the queue is untouched and [`TOPQUEUE_ERROR`] is
+/// returned.
+///
+/// # Safety
+///
+/// `queue` must be null or a live handle from [`topqueue_i64_new`], and `out`
+/// must be null or valid for `out_len` writes.
+#[no_mangle]
+pub unsafe extern "C" fn topqueue_i64_drain_sorted(
+    queue: *mut TopQueueI64,
+    out: *mut i64,
+    out_len: usize,
+) -> usize {
+    match queue.as_mut() {
+        Some(q) => drain_into(&mut q.0, out, out_len, |n| n),
+        None => TOPQUEUE_ERROR,
+    }
+}
+
+/// Frees the queue that `*queue` points to, and sets `*queue` to null.
+///
+/// Does nothing if `queue` or `*queue` is null.
+///
+/// # Safety
+///
+/// `queue` must be null or point to a handle that is null or was returned by
+/// [`topqueue_i64_new`].
+#[no_mangle]
+pub unsafe extern "C" fn topqueue_i64_free(queue: *mut *mut TopQueueI64) {
+    free(queue);
+}
+
+/// Creates a queue that retains the largest `capacity` `f64`s pushed into it.
+///
+/// The queue must be released with [`topqueue_f64_free`].
+#[no_mangle]
+pub extern "C" fn topqueue_f64_new(capacity: usize) -> *mut TopQueueF64 {
+    Box::into_raw(Box::new(TopQueueF64(TopQueue::new(capacity))))
+}
+
+/// Pushes `item` into the queue. Returns 0, or -1 if `queue` is null.
+///
+/// # Safety
+///
+/// `queue` must be null or a live handle from [`topqueue_f64_new`].
+#[no_mangle]
+pub unsafe extern "C" fn topqueue_f64_push(queue: *mut TopQueueF64, item: f64) -> i32 {
+    match queue.as_mut() {
+        Some(q) => {
+            q.0.push(OrdF64(item));
+            0
+        }
+        None => -1,
+    }
+}
+
+/// Returns the number of items in the queue, or 0 if `queue` is null.
+///
+/// # Safety
+///
+/// `queue` must be null or a live handle from [`topqueue_f64_new`].
+#[no_mangle]
+pub unsafe extern "C" fn topqueue_f64_len(queue: *const TopQueueF64) -> usize {
+    queue.as_ref().map_or(0, |q| q.0.len())
+}
+
+/// Empties the queue into `out`, smallest first, and returns the number of
+/// items written.
+///
+/// Fails as [`topqueue_i64_drain_sorted`] does.
+///
+/// # Safety
+///
+/// `queue` must be null or a live handle from [`topqueue_f64_new`], and `out`
+/// must be null or valid for `out_len` writes.
+#[no_mangle]
+pub unsafe extern "C" fn topqueue_f64_drain_sorted(
+    queue: *mut TopQueueF64,
+    out: *mut f64,
+    out_len: usize,
+) -> usize {
+    match queue.as_mut() {
+        Some(q) => drain_into(&mut q.0, out, out_len, |n| n.0),
+        None => TOPQUEUE_ERROR,
+    }
+}
+
+/// Frees the queue that `*queue` points to, and sets `*queue` to null.
+///
+/// Does nothing if `queue` or `*queue` is null.
+///
+/// # Safety
+///
+/// `queue` must be null or point to a handle that is null or was returned by
+/// [`topqueue_f64_new`].
+#[no_mangle]
+pub unsafe extern "C" fn topqueue_f64_free(queue: *mut *mut TopQueueF64) {
+    free(queue);
+}
+
+unsafe fn drain_into<T: Ord, U>(
+    queue: &mut TopQueue<T>,
+    out: *mut U,
+    out_len: usize,
+    convert: impl Fn(T) -> U,
+) -> usize {
+    if out.is_null() || out_len < queue.len() {
+        return TOPQUEUE_ERROR;
+    }
+    let mut written = 0;
+    for item in queue.drain_sorted() {
+        out.add(written).write(convert(item));
+        written += 1;
+    }
+    written
+}
+
+unsafe fn free<Q>(queue: *mut *mut Q) {
+    if let Some(handle) = queue.as_mut() {
+        let q = ptr::replace(handle, ptr::null_mut());
+        if !q.is_null() {
+            drop(Box::from_raw(q));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+
+    #[test]
+    fn ffi_i64_round_trip() {
+        let mut q = topqueue_i64_new(3);
+        unsafe {
+            for n in [5, -2, 9, 7, 1] {
+                assert_eq!(0, topqueue_i64_push(q, n));
+            }
+            assert_eq!(3, topqueue_i64_len(q));
+
+            let mut out = [0_i64; 3];
+            assert_eq!(
+                TOPQUEUE_ERROR,
+                topqueue_i64_drain_sorted(q, out.as_mut_ptr(), 2)
+            );
+            assert_eq!(3, topqueue_i64_drain_sorted(q, out.as_mut_ptr(), 3));
+            assert_eq!([5, 7, 9], out);
+            assert_eq!(0, topqueue_i64_len(q));
+
+            topqueue_i64_free(&raw mut q);
+            assert!(q.is_null());
+            topqueue_i64_free(&raw mut q);
+        }
+    }
+
+    #[test]
+    fn ffi_f64_round_trip() {
+        let mut q = topqueue_f64_new(2);
+        unsafe {
+            for n in [0.5, 2.25, -1.0, 1.5] {
+                topqueue_f64_push(q, n);
+            }
+            let mut out = [0.0; 4];
+            assert_eq!(2, topqueue_f64_drain_sorted(q, out.as_mut_ptr(), 4));
+            assert_eq!(
+                [OrdF64(1.5), OrdF64(2.25)],
+                [OrdF64(out[0]), OrdF64(out[1])]
+            );
+            topqueue_f64_free(&raw mut q);
+        }
+    }
+
+    #[test]
+    fn ffi_tolerates_null_pointers() {
+        unsafe {
+            assert_eq!(-1, topqueue_i64_push(ptr::null_mut(), 1));
+            assert_eq!(0, topqueue_i64_len(ptr::null()));
+            assert_eq!(
+                TOPQUEUE_ERROR,
+                topqueue_i64_drain_sorted(ptr::null_mut(), ptr::null_mut(), 0)
+            );
+            topqueue_i64_free(ptr::null_mut());
+
+            let mut q = topqueue_f64_new(1);
+            assert_eq!(
+                TOPQUEUE_ERROR,
+                topqueue_f64_drain_sorted(q, ptr::null_mut(), 1)
+            );
+            topqueue_f64_free(&raw mut q);
+            assert_eq!(-1, topqueue_f64_push(q, 1.0));
+        }
+    }
+}
@@ -0,0 +1,730 @@
+//! Min-heaps that can back a [`TopQueue`].
+//!
+//! A `TopQueue` only ever needs a min-heap: it inspects and evicts its
+//! smallest item. By default that is `std`'s `BinaryHeap<Reverse<T>>`, but
+//! any [`Heap`] can be chosen with [`TopQueue::with_heap`]:
+//!
+//! - [`DaryHeap`] stores `D` children per node. Wider nodes make the tree
+//!   shallower, which trades more comparisons per level for fewer levels and
+//!   better cache behavior.
+//! - [`MinMaxHeap`] also finds its *largest* item in *O*(1), so a `TopQueue`
+//!   backed by one can report both ends of its contents cheaply.
+//!
+//! ```
+//! # use topqueue::heap::{DaryHeap, MinMaxHeap};
+//! # use topqueue::topqueue_final::TopQueue;
+//! let mut q = TopQueue::new(3).with_heap::<DaryHeap<_, 4>>();
+//! for n in [4, 1, 7, 3, 9] {
+//!     q.push(n);
+//! }
+//! assert_eq!(vec![4, 7, 9], q.into_vec());
+//!
+//! let q = TopQueue::from_iter(3, [4, 1, 7, 3, 9]).with_heap::<MinMaxHeap<_>>();
+//! assert_eq!(Some(&9), q.peek_max());
+//! ```
+//!
+//! [`TopQueue`]: crate::topqueue_final::TopQueue
+//! [`TopQueue::with_heap`]: crate::topqueue_final::TopQueue::with_heap
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, TryReserveError};
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::mem;
+
+/// A min-heap, as used by `TopQueue` to hold its items.
+///
+/// This trait is sealed and cannot be implemented outside this crate.
+pub trait Heap<T: Ord>: private::Sealed {
+    /// Creates an empty heap with room for `capacity` items.
+    fn with_capacity(capacity: usize) -> Self;
+
+    /// Creates a heap from `items` in *O*(*n*).
+    fn from_vec(items: Vec<T>) -> Self;
+
+    /// Returns the number of items in the heap.
+    fn len(&self) -> usize;
+
+    /// Returns true if the heap is empty.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the smallest item.
+    fn peek_min(&self) -> Option<&T>;
+
+    /// Adds `item` to the heap.
+    fn push(&mut self, item: T);
+
+    /// Removes and returns the smallest item.
+    fn pop_min(&mut self) -> Option<T>;
+
+    /// Replaces the smallest item with `item` in place and returns it. This
+    /// takes a single sift, rather than the two of a `pop_min` and `push`.
+    ///
+    /// If the heap is empty, `item` is pushed and `None` is returned.
+    fn replace_min(&mut self, item: T) -> Option<T> {
+        let mut min = None;
+        self.replace_min_with(item, |old, _| min = old);
+        min
+    }
+
+    /// Replaces the smallest item with `item` in place, as
+    /// [`replace_min`](Heap::replace_min) does, calling `f` with the old
+    /// smallest and then `item` before `item` is sifted into place.
+    fn replace_min_with<F: FnOnce(Option<T>, &T)>(&mut self, item: T, f: F);
+
+    /// Retains only the items for which `f` returns `true`.
+    fn retain<F: FnMut(&T) -> bool>(&mut self, f: F);
+
+    /// Removes every item.
+    fn clear(&mut self);
+
+    /// Returns the number of items the heap can hold without reallocating.
+    fn capacity(&self) -> usize;
+
+    /// Tries to make room for exactly `additional` more items.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the memory can't be allocated, leaving the heap unchanged.
+    fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError>;
+
+    /// Releases unused memory.
+    fn shrink_to_fit(&mut self);
+
+    /// Returns the items in the heap's internal order.
+    fn items<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a;
+
+    /// Returns the items in unspecified order.
+    fn into_vec(self) -> Vec<T>;
+
+    /// Returns the items in ascending order.
+    fn into_sorted_vec(self) -> Vec<T>;
+}
+
+mod private {
+    pub trait Sealed {}
+
+    impl<T: Ord> Sealed for std::collections::BinaryHeap<std::cmp::Reverse<T>> {}
+    impl<T: Ord, const D: usize> Sealed for super::DaryHeap<T, D> {}
+    impl<T: Ord> Sealed for super::MinMaxHeap<T> {}
+}
+
+impl<T: Ord> Heap<T> for BinaryHeap<Reverse<T>> {
+    fn with_capacity(capacity: usize) -> Self {
+        BinaryHeap::with_capacity(capacity)
+    }
+
+    fn from_vec(items: Vec<T>) -> Self {
+        items.into_iter().map(Reverse).collect()
+    }
+
+    fn len(&self) -> usize {
+        BinaryHeap::len(self)
+    }
+
+    fn peek_min(&self) -> Option<&T> {
+        self.peek().map(|r| &r.0)
+    }
+
+    fn push(&mut self, item: T) {
+        BinaryHeap::push(self, Reverse(item));
+    }
+
+    fn pop_min(&mut self) -> Option<T> {
+        self.pop().map(|r| r.0)
+    }
+
+    fn replace_min_with<F: FnOnce(Option<T>, &T)>(&mut self, item: T, f: F) {
+        let Some(mut root) = self.peek_mut() else {
+            f(None, &item);
+            BinaryHeap::push(self, Reverse(item));
+            return;
+        };
+        let min = mem::replace(&mut root.0, item);
+        f(Some(min), &root.0);
+        // `PeekMut` sifts the new root down when it's dropped.
+    }
+
+    fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        BinaryHeap::retain(self, |r| f(&r.0));
+    }
+
+    fn clear(&mut self) {
+        BinaryHeap::clear(self);
+    }
+
+    fn capacity(&self) -> usize {
+        BinaryHeap::capacity(self)
+    }
+
+    fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        BinaryHeap::try_reserve_exact(self, additional)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        BinaryHeap::shrink_to_fit(self);
+    }
+
+    fn items<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        self.iter().map(|r| &r.0)
+    }
+
+    fn into_vec(self) -> Vec<T> {
+        BinaryHeap::into_vec(self)
+            .into_iter()
+            .map(|r| r.0)
+            .collect()
+    }
+
+    fn into_sorted_vec(self) -> Vec<T> {
+        // Sorted ascending by `Reverse`, which is descending by `T`.
+        BinaryHeap::into_sorted_vec(self)
+            .into_iter()
+            .rev()
+            .map(|r| r.0)
+            .collect()
+    }
+}
+
+/// A min-heap where each node has `D` children.
+///
+/// `D` must be at least 2; 2, 4 and 8 are the usual choices.
+#[derive(Debug, Clone)]
+pub struct DaryHeap<T, const D: usize> {
+    items: Vec<T>,
+}
+
+impl<T: Ord, const D: usize> DaryHeap<T, D> {
+    const VALID_ARITY: () = assert!(D >= 2, "a heap needs at least 2 children per node");
+
+    /// Creates an empty heap.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    fn heapify(&mut self) {
+        for i in (0..=self.items.len() / D).rev() {
+            self.sift_down(i);
+        }
+    }
+
+    fn sift_up(&mut self, mut child: usize) {
+        while child > 0 {
+            let parent = (child - 1) / D;
+            if self.items[child] >= self.items[parent] {
+                return;
+            }
+            self.items.swap(child, parent);
+            child = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut parent: usize) {
+        let len = self.items.len();
+        loop {
+            let first = parent * D + 1;
+            if first >= len {
+                return;
+            }
+            let end = (first + D).min(len);
+            let least = (first + 1..end).fold(first, |least, i| {
+                if self.items[i] < self.items[least] {
+                    i
+                } else {
+                    least
+                }
+            });
+            if self.items[least] >= self.items[parent] {
+                return;
+            }
+            self.items.swap(parent, least);
+            parent = least;
+        }
+    }
+}
+
+impl<T: Ord, const D: usize> Default for DaryHeap<T, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord, const D: usize> Heap<T> for DaryHeap<T, D> {
+    fn with_capacity(capacity: usize) -> Self {
+        let () = Self::VALID_ARITY;
+        DaryHeap {
+            items: Vec::with_capacity(capacity),
+        }
+    }
+
+    fn from_vec(items: Vec<T>) -> Self {
+        let () = Self::VALID_ARITY;
+        let mut heap = DaryHeap { items };
+        heap.heapify();
+        heap
+    }
+
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    fn peek_min(&self) -> Option<&T> {
+        self.items.first()
+    }
+
+    fn push(&mut self, item: T) {
+        self.items.push(item);
+        self.sift_up(self.items.len() - 1);
+    }
+
+    fn pop_min(&mut self) -> Option<T> {
+        if self.items.is_empty() {
+            return None;
+        }
+        let min = self.items.swap_remove(0);
+        self.sift_down(0);
+        Some(min)
+    }
+
+    fn replace_min_with<F: FnOnce(Option<T>, &T)>(&mut self, item: T, f: F) {
+        let Some(root) = self.items.first_mut() else {
+            f(None, &item);
+            self.items.push(item);
+            return;
+        };
+        let min = mem::replace(root, item);
+        f(Some(min), root);
+        self.sift_down(0);
+    }
+
+    fn retain<F: FnMut(&T) -> bool>(&mut self, f: F) {
+        self.items.retain(f);
+        self.heapify();
+    }
+
+    fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    fn capacity(&self) -> usize {
+        self.items.capacity()
+    }
+
+    fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.items.try_reserve_exact(additional)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.items.shrink_to_fit();
+    }
+
+    fn items<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        self.items.iter()
+    }
+
+    fn into_vec(self) -> Vec<T> {
+        self.items
+    }
+
+    fn into_sorted_vec(mut self) -> Vec<T> {
+        self.items.sort_unstable();
+        self.items
+    }
+}
+
+impl<T: Ord, const D: usize> IntoIterator for DaryHeap<T, D> {
+    type Item = T;
+    type IntoIter = IntoIterSorted<T, Self>;
+
+    /// Returns the items in ascending order.
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIterSorted {
+            heap: self,
+            item: PhantomData,
+        }
+    }
+}
+
+/// A min-max heap, which finds both its smallest and largest items in *O*(1).
+///
+/// Nodes on even levels of the tree are no larger than any of their
+/// descendants, and nodes on odd levels are no smaller, so the root is the
+/// smallest item and the larger of its children is the largest.
+#[derive(Debug, Clone)]
+pub struct MinMaxHeap<T> {
+    items: Vec<T>,
+}
+
+impl<T: Ord> MinMaxHeap<T> {
+    /// Creates an empty heap.
+    #[must_use]
+    pub fn new() -> Self {
+        MinMaxHeap { items: Vec::new() }
+    }
+
+    /// Returns the largest item.
+    #[must_use]
+    pub fn peek_max(&self) -> Option<&T> {
+        self.max_index().map(|i| &self.items[i])
+    }
+
+    /// Removes and returns the largest item.
+    pub fn pop_max(&mut self) -> Option<T> {
+        let i = self.max_index()?;
+        let max = self.items.swap_remove(i);
+        if i < self.items.len() {
+            self.trickle_down(i);
+        }
+        Some(max)
+    }
+
+    fn max_index(&self) -> Option<usize> {
+        match self.items.len() {
+            0 => None,
+            1 => Some(0),
+            2 => Some(1),
+            _ if self.items[2] > self.items[1] => Some(2),
+            _ => Some(1),
+        }
+    }
+
+    fn is_min_level(i: usize) -> bool {
+        (i + 1).ilog2().is_multiple_of(2)
+    }
+
+    /// Returns true if the item at `a` belongs above the item at `b`, for a
+    /// node on a min level if `min` and on a max level otherwise.
+    fn before(&self, a: usize, b: usize, min: bool) -> bool {
+        if min {
+            self.items[a] < self.items[b]
+        } else {
+            self.items[a] > self.items[b]
+        }
+    }
+
+    fn heapify(&mut self) {
+        for i in (0..self.items.len() / 2).rev() {
+            self.trickle_down(i);
+        }
+    }
+
+    fn bubble_up(&mut self, i: usize) {
+        if i == 0 {
+            return;
+        }
+        let parent = (i - 1) / 2;
+        let min = Self::is_min_level(i);
+        // An item on a min level larger than its parent on a max level
+        // belongs among the max levels instead, and vice versa.
+        if self.before(parent, i, min) {
+            self.items.swap(i, parent);
+            self.bubble_up_level(parent, !min);
+        } else {
+            self.bubble_up_level(i, min);
+        }
+    }
+
+    /// Moves the item at `i` up through its grandparents on the same kind
+    /// of level.
+    fn bubble_up_level(&mut self, mut i: usize, min: bool) {
+        while i > 2 {
+            let grandparent = ((i - 1) / 2 - 1) / 2;
+            if !self.before(i, grandparent, min) {
+                return;
+            }
+            self.items.swap(i, grandparent);
+            i = grandparent;
+        }
+    }
+
+    fn trickle_down(&mut self, mut i: usize) {
+        let min = Self::is_min_level(i);
+        let len = self.items.len();
+        loop {
+            let first_child = 2 * i + 1;
+            if first_child >= len {
+                return;
+            }
+            // The best of the children and grandchildren.
+            let first_grandchild = 2 * first_child + 1;
+            let descendants = (first_child..(first_child + 2).min(len))
+                .chain(first_grandchild..(first_grandchild + 4).min(len));
+            let best = descendants.fold(first_child, |best, d| {
+                if self.before(d, best, min) {
+                    d
+                } else {
+                    best
+                }
+            });
+            if !self.before(best, i, min) {
+                return;
+            }
+            self.items.swap(i, best);
+            if best < first_grandchild {
+                return;
+            }
+            let parent = (best - 1) / 2;
+            if self.before(parent, best, min) {
+                self.items.swap(best, parent);
+            }
+            i = best;
+        }
+    }
+}
+
+impl<T: Ord> Default for MinMaxHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> Heap<T> for MinMaxHeap<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        MinMaxHeap {
+            items: Vec::with_capacity(capacity),
+        }
+    }
+
+    fn from_vec(items: Vec<T>) -> Self {
+        let mut heap = MinMaxHeap { items };
+        heap.heapify();
+        heap
+    }
+
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    fn peek_min(&self) -> Option<&T> {
+        self.items.first()
+    }
+
+    fn push(&mut self, item: T) {
+        self.items.push(item);
+        self.bubble_up(self.items.len() - 1);
+    }
+
+    fn pop_min(&mut self) -> Option<T> {
+        if self.items.is_empty() {
+            return None;
+        }
+        let min = self.items.swap_remove(0);
+        if !self.items.is_empty() {
+            self.trickle_down(0);
+        }
+        Some(min)
+    }
+
+    fn replace_min_with<F: FnOnce(Option<T>, &T)>(&mut self, item: T, f: F) {
+        let Some(root) = self.items.first_mut() else {
+            f(None, &item);
+            self.items.push(item);
+            return;
+        };
+        let min = mem::replace(root, item);
+        f(Some(min), root);
+        self.trickle_down(0);
+    }
+
+    fn retain<F: FnMut(&T) -> bool>(&mut self, f: F) {
+        self.items.retain(f);
+        self.heapify();
+    }
+
+    fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    fn capacity(&self) -> usize {
+        self.items.capacity()
+    }
+
+    fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.items.try_reserve_exact(additional)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.items.shrink_to_fit();
+    }
+
+    fn items<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        self.items.iter()
+    }
+
+    fn into_vec(self) -> Vec<T> {
+        self.items
+    }
+
+    fn into_sorted_vec(mut self) -> Vec<T> {
+        self.items.sort_unstable();
+        self.items
+    }
+}
+
+impl<T: Ord> IntoIterator for MinMaxHeap<T> {
+    type Item = T;
+    type IntoIter = IntoIterSorted<T, Self>;
+
+    /// Returns the items in ascending order.
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIterSorted {
+            heap: self,
+            item: PhantomData,
+        }
+    }
+}
+
+/// An iterator over the items of a heap in ascending order.
+#[derive(Debug, Clone)]
+pub struct IntoIterSorted<T, H> {
+    heap: H,
+    item: PhantomData<T>,
+}
+
+impl<T, H> IntoIterSorted<T, H> {
+    pub(crate) fn new(heap: H) -> Self {
+        IntoIterSorted {
+            heap,
+            item: PhantomData,
+        }
+    }
+}
+
+impl<T: Ord, H: Heap<T>> Iterator for IntoIterSorted<T, H> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.heap.pop_min()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.heap.len(), Some(self.heap.len()))
+    }
+}
+
+impl<T: Ord, H: Heap<T>> ExactSizeIterator for IntoIterSorted<T, H> {}
+
+impl<T: Ord, H: Heap<T>> FusedIterator for IntoIterSorted<T, H> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::rands;
+
+    fn assert_dary<T: Ord + std::fmt::Debug, const D: usize>(heap: &DaryHeap<T, D>) {
+        for child in 1..heap.items.len() {
+            let parent = (child - 1) / D;
+            assert!(heap.items[parent] <= heap.items[child], "{heap:?}");
+        }
+    }
+
+    /// Checks each item against its parent and grandparent, which by
+    /// transitivity covers all of its ancestors.
+    fn assert_min_max<T: Ord + std::fmt::Debug>(heap: &MinMaxHeap<T>) {
+        let items = &heap.items;
+        for i in 1..items.len() {
+            let parent = (i - 1) / 2;
+            let grandparent = (parent > 0).then(|| (parent - 1) / 2);
+            if MinMaxHeap::<T>::is_min_level(i) {
+                assert!(items[i] <= items[parent], "{heap:?}");
+                assert!(grandparent.is_none_or(|g| items[g] <= items[i]), "{heap:?}");
+            } else {
+                assert!(items[i] >= items[parent], "{heap:?}");
+                assert!(grandparent.is_none_or(|g| items[g] >= items[i]), "{heap:?}");
+            }
+        }
+    }
+
+    /// Drives `heap` through random operations alongside a sorted `Vec`.
+    fn check_against_sorted_vec<H: Heap<u8>>(mut heap: H, check: impl Fn(&H)) {
+        let mut oracle: Vec<u8> = Vec::new();
+        for op in rands::<u8>().take(5000) {
+            match op % 4 {
+                0 | 1 => {
+                    heap.push(op);
+                    oracle.insert(oracle.partition_point(|&n| n <= op), op);
+                }
+                2 => assert_eq!(
+                    (!oracle.is_empty()).then(|| oracle.remove(0)),
+                    heap.pop_min()
+                ),
+                _ => {
+                    let old = (!oracle.is_empty()).then(|| oracle.remove(0));
+                    assert_eq!(old, heap.replace_min(op));
+                    oracle.insert(oracle.partition_point(|&n| n <= op), op);
+                }
+            }
+            check(&heap);
+            assert_eq!(oracle.first(), heap.peek_min());
+            assert_eq!(oracle.len(), heap.len());
+        }
+        heap.retain(|n| n % 3 != 0);
+        oracle.retain(|n| n % 3 != 0);
+        check(&heap);
+        assert_eq!(oracle, heap.into_sorted_vec());
+    }
+
+    #[test]
+    fn dary_heaps_behave_like_sorted_vecs() {
+        check_against_sorted_vec(DaryHeap::<_, 2>::new(), assert_dary);
+        check_against_sorted_vec(DaryHeap::<_, 4>::new(), assert_dary);
+        check_against_sorted_vec(DaryHeap::<_, 8>::new(), assert_dary);
+    }
+
+    #[test]
+    fn min_max_heap_behaves_like_sorted_vec() {
+        check_against_sorted_vec(MinMaxHeap::new(), assert_min_max);
+    }
+
+    #[test]
+    fn binary_heap_behaves_like_sorted_vec() {
+        check_against_sorted_vec(BinaryHeap::new(), |_| ());
+    }
+
+    #[test]
+    fn min_max_heap_pops_from_both_ends() {
+        let nums: Vec<u16> = rands().take(1000).collect();
+        let mut heap = MinMaxHeap::from_vec(nums.clone());
+        assert_min_max(&heap);
+
+        let mut sorted = nums;
+        sorted.sort_unstable();
+        while !sorted.is_empty() {
+            assert_eq!(sorted.last(), heap.peek_max());
+            assert_eq!(sorted.pop(), heap.pop_max());
+            assert_min_max(&heap);
+            if let Some(min) = (!sorted.is_empty()).then(|| sorted.remove(0)) {
+                assert_eq!(Some(min), heap.pop_min());
+            }
+        }
+        assert_eq!(None, heap.pop_max());
+    }
+
+    #[test]
+    fn heaps_iterate_in_sorted_order() {
+        let nums = vec![5, 3, 8, 1, 9, 2, 7];
+        let mut sorted = nums.clone();
+        sorted.sort_unstable();
+
+        let dary = DaryHeap::<_, 4>::from_vec(nums.clone());
+        assert_dary(&dary);
+        assert_eq!(sorted, dary.into_iter().collect::<Vec<_>>());
+        let min_max = MinMaxHeap::from_vec(nums);
+        assert_eq!(7, min_max.clone().into_iter().len());
+        assert_eq!(sorted, min_max.into_iter().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,264 @@
+//! Counting the work done by top-k strategies, with the `instrument` feature.
+//!
+//! Comparisons are tallied on a per-thread counter by [`Counted`] items and
+//! by comparators wrapped with [`counting`]. With the feature enabled,
+//! [`util::get_top`] and [`topqueue_basic::TopQueue`] count their own
+//! comparisons too, and the basic queue keeps [`Stats`] of its pushes. For a
+//! [`TopQueue`], push [`Counted`] items and watch it with [`Stats`], which is
+//! an [`Observer`].
+//!
+//! Without the feature none of this exists, and the queues and `get_top` are
+//! exactly as they'd otherwise be.
+//!
+//! [`compare`] runs all three strategies over the same input and reports the
+//! work each did:
+//!
+//! ```
+//! # use topqueue::instrument::compare;
+//! let nums: Vec<i32> = (0..10_000).collect();
+//! for report in compare(&nums, 10) {
+//!     println!("{report}");
+//! }
+//! ```
+//!
+//! [`util::get_top`]: crate::util::get_top
+//! [`topqueue_basic::TopQueue`]: crate::topqueue_basic::TopQueue
+
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::observer::Observer;
+use crate::topqueue_basic;
+use crate::topqueue_final::TopQueue;
+use crate::util::get_top;
+
+thread_local! {
+    static COMPARISONS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Returns the number of comparisons counted on this thread so far.
+#[must_use]
+pub fn comparisons() -> u64 {
+    COMPARISONS.with(Cell::get)
+}
+
+/// Runs `f`, returning its result and the number of comparisons it made.
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, u64) {
+    let before = comparisons();
+    let result = f();
+    (result, comparisons() - before)
+}
+
+pub(crate) fn count_comparison() {
+    COMPARISONS.with(|c| c.set(c.get() + 1));
+}
+
+/// Wraps a comparator so that each call is counted.
+///
+/// ```
+/// # use topqueue::instrument::{counting, measure};
+/// let mut words = vec!["pear", "fig", "apple"];
+/// let ((), comparisons) = measure(|| words.sort_by(counting(|a: &&str, b| a.len().cmp(&b.len()))));
+/// assert_eq!(vec!["fig", "pear", "apple"], words);
+/// assert!(comparisons >= 2);
+/// ```
+pub fn counting<T>(mut cmp: impl FnMut(&T, &T) -> Ordering) -> impl FnMut(&T, &T) -> Ordering {
+    move |a, b| {
+        count_comparison();
+        cmp(a, b)
+    }
+}
+
+/// An item whose every comparison is counted.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(transparent)]
+pub struct Counted<T>(pub T);
+
+impl<T: PartialEq> PartialEq for Counted<T> {
+    fn eq(&self, other: &Self) -> bool {
+        count_comparison();
+        self.0 == other.0
+    }
+}
+
+impl<T: Eq> Eq for Counted<T> {}
+
+impl<T: Ord> PartialOrd for Counted<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for Counted<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        count_comparison();
+        self.0.cmp(&other.0)
+    }
+}
+
+/// Counts of what pushes into a queue have done.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Stats {
+    /// Items pushed.
+    pub pushes: u64,
+    /// Pushed items the queue refused.
+    pub rejections: u64,
+    /// Items evicted to make room for pushed ones.
+    pub replacements: u64,
+}
+
+impl<T> Observer<T> for Stats {
+    fn on_insert(&mut self, _item: &T) {
+        self.pushes += 1;
+    }
+
+    fn on_evict(&mut self, _item: T) {
+        self.replacements += 1;
+    }
+
+    fn on_reject(&mut self, _item: T) {
+        self.pushes += 1;
+        self.rejections += 1;
+    }
+}
+
+/// The work one strategy did to find the top `k` of `n` items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Report {
+    /// The strategy's name.
+    pub strategy: &'static str,
+    /// The number of items considered.
+    pub n: usize,
+    /// The number of items kept.
+    pub k: usize,
+    /// The queue's push counts, all zero for strategies without a queue.
+    pub stats: Stats,
+    /// Comparisons made between items.
+    pub comparisons: u64,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<14} n={} k={} pushes={} rejections={} replacements={} comparisons={}",
+            self.strategy,
+            self.n,
+            self.k,
+            self.stats.pushes,
+            self.stats.rejections,
+            self.stats.replacements,
+            self.comparisons
+        )
+    }
+}
+
+/// Finds the top `k` of `nums` with [`get_top`], the basic queue and the
+/// final queue, and reports the work each did.
+#[must_use]
+pub fn compare(nums: &[i32], k: usize) -> Vec<Report> {
+    let report = |strategy, stats, comparisons| Report {
+        strategy,
+        n: nums.len(),
+        k,
+        stats,
+        comparisons,
+    };
+
+    let (_, sorting) = measure(|| get_top(nums, k));
+
+    let mut basic = topqueue_basic::TopQueue::new(k);
+    let ((), basic_comparisons) = measure(|| nums.iter().for_each(|&n| basic.push(n)));
+
+    let mut queue = TopQueue::new(k).with_observer(Stats::default());
+    let ((), final_comparisons) = measure(|| nums.iter().for_each(|&n| queue.push(Counted(n))));
+
+    vec![
+        report("get_top", Stats::default(), sorting),
+        report("topqueue_basic", basic.stats(), basic_comparisons),
+        report("topqueue_final", *queue.observer(), final_comparisons),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare, measure, Counted, Report, Stats};
+    use crate::topqueue_final::TopQueue;
+    use crate::util::rands;
+
+    /// The comparisons a heap of `k` items may make per push: one against
+    /// the smallest item, then two per level as a new item sinks.
+    fn per_push_bound(k: usize) -> u64 {
+        2 * u64::from((k + 1).next_power_of_two().ilog2()) + 1
+    }
+
+    #[test]
+    fn heap_comparisons_are_n_log_k() {
+        // Ascending input is the worst case: every item replaces the root.
+        let ascending: Vec<i32> = (0..20_000).collect();
+        let random: Vec<i32> = rands().take(ascending.len()).collect();
+        for k in [1, 16, 100, 1000] {
+            for nums in [&ascending, &random] {
+                let [sort, basic, fin]: [Report; 3] = compare(nums, k).try_into().unwrap();
+                let bound = 20_000 * per_push_bound(k);
+                assert!(fin.comparisons <= bound, "{fin} exceeds {bound}");
+                // Popping then pushing sinks to the bottom, then rises.
+                assert!(
+                    basic.comparisons <= 2 * bound,
+                    "{basic} exceeds {}",
+                    2 * bound
+                );
+                // Sorting does better on input that's already in order.
+                if k <= 100 && nums == &random {
+                    assert!(fin.comparisons < sort.comparisons, "{fin} vs {sort}");
+                }
+            }
+        }
+
+        let worst = |k| compare(&ascending, k)[2].comparisons;
+        // 64 times the capacity is only about 2.5 times the work.
+        assert!(worst(1024) < 3 * worst(16));
+    }
+
+    #[test]
+    fn stats_count_pushes() {
+        let nums = [5, 1, 9, 3, 7, 2];
+        let [sort, basic, fin]: [Report; 3] = compare(&nums, 3).try_into().unwrap();
+        let expected = Stats {
+            pushes: 6,
+            rejections: 1,
+            replacements: 2,
+        };
+        assert_eq!(expected, basic.stats);
+        assert_eq!(expected, fin.stats);
+        assert_eq!(Stats::default(), sort.stats);
+        assert_eq!(
+            "topqueue_final n=6 k=3 pushes=6 rejections=1 replacements=2 comparisons=",
+            fin.to_string().trim_end_matches(char::is_numeric)
+        );
+    }
+
+    #[test]
+    fn counted_items_count_comparisons() {
+        let mut q = TopQueue::new(1);
+        let ((), comparisons) = measure(|| {
+            q.push(Counted(1));
+            q.push(Counted(2));
+        });
+        // Only the second push compares, against the first.
+        assert_eq!(1, comparisons);
+        assert!(Counted('a') < Counted('b'));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn reports_serialize() {
+        let report = &compare(&[3, 1, 2], 2)[1];
+        let json = serde_json::to_value(report).unwrap();
+        assert_eq!("topqueue_basic", json["strategy"]);
+        assert_eq!(3, json["stats"]["pushes"]);
+    }
+}
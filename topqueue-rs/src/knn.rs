@@ -0,0 +1,349 @@
+//! Exact k-nearest-neighbour search by brute force.
+//!
+//! For a few thousand embeddings, comparing a query against every one is
+//! fast enough and always exact. [`knn`] does that, keeping the `k` nearest
+//! in a [`TopQueue`] of their distances, reversed so that the queue retains
+//! the smallest:
+//!
+//! ```
+//! # use topqueue::knn::{knn, Metric};
+//! let points = [[0.0, 0.0], [1.0, 1.0], [5.0, 5.0], [0.5, 0.0]];
+//! let nearest = knn(&[0.0, 0.1], &points, 2, Metric::L2);
+//! assert_eq!(vec![0, 3], nearest.iter().map(|n| n.index).collect::<Vec<_>>());
+//! assert!((nearest[0].distance - 0.1).abs() < 1e-6);
+//! ```
+//!
+//! [`knn_batch`] answers many queries in one pass, comparing each block of
+//! points against every query while the block is in cache. With the `rayon`
+//! feature, `par_knn` and `par_knn_batch` spread the work over threads, with
+//! the same results.
+//!
+//! Neighbours are returned nearest first. Points at equal distances are
+//! ranked by index, lowest first, so every function agrees exactly.
+
+use std::cmp::Reverse;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::topqueue_final::TopQueue;
+use crate::util::OrdF64;
+
+/// How many points [`knn_batch`] compares against every query at a time.
+const BLOCK: usize = 256;
+
+/// How distance between vectors is measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// Euclidean distance.
+    L2,
+    /// One minus the cosine of the angle between the vectors, from 0 for
+    /// the same direction to 2 for opposite ones. A zero vector is at
+    /// distance 1 from everything.
+    Cosine,
+    /// The negated dot product, so that the largest product is nearest.
+    Dot,
+}
+
+impl Metric {
+    /// Returns the distance between `a` and `b`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the vectors differ in length.
+    #[must_use]
+    pub fn distance(self, a: &[f32], b: &[f32]) -> f64 {
+        Query::new(a, self).distance(b)
+    }
+}
+
+/// One of the nearest points to a query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbor {
+    /// The point's index in the slice searched.
+    pub index: usize,
+    /// The point's distance from the query.
+    pub distance: f64,
+}
+
+/// A query vector, with anything its metric needs precomputed.
+struct Query<'a> {
+    vector: &'a [f32],
+    metric: Metric,
+    norm: f64,
+}
+
+impl<'a> Query<'a> {
+    fn new(vector: &'a [f32], metric: Metric) -> Self {
+        let norm = match metric {
+            Metric::Cosine => dot(vector, vector).sqrt(),
+            Metric::L2 | Metric::Dot => 0.0,
+        };
+        Query {
+            vector,
+            metric,
+            norm,
+        }
+    }
+
+    fn distance(&self, point: &[f32]) -> f64 {
+        assert_eq!(
+            self.vector.len(),
+            point.len(),
+            "vectors must have the same dimension"
+        );
+        match self.metric {
+            Metric::L2 => self
+                .vector
+                .iter()
+                .zip(point)
+                .map(|(&a, &b)| (f64::from(a) - f64::from(b)).powi(2))
+                .sum::<f64>()
+                .sqrt(),
+            Metric::Cosine => {
+                let norms = self.norm * dot(point, point).sqrt();
+                if norms == 0.0 {
+                    1.0
+                } else {
+                    1.0 - dot(self.vector, point) / norms
+                }
+            }
+            Metric::Dot => -dot(self.vector, point),
+        }
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(&a, &b)| f64::from(a) * f64::from(b))
+        .sum()
+}
+
+/// The `k` nearest points seen so far, as a queue of the largest reversed
+/// distances, ties going to the lowest index.
+type Nearest = TopQueue<Reverse<(OrdF64, usize)>>;
+
+fn push(nearest: &mut Nearest, query: &Query<'_>, index: usize, point: &[f32]) {
+    nearest.push(Reverse((OrdF64(query.distance(point)), index)));
+}
+
+fn neighbors(nearest: Nearest) -> Vec<Neighbor> {
+    // The queue yields its smallest reversed distance, so the farthest, first.
+    let mut neighbors: Vec<Neighbor> = nearest
+        .into_iter()
+        .map(|Reverse((distance, index))| Neighbor {
+            index,
+            distance: distance.0,
+        })
+        .collect();
+    neighbors.reverse();
+    neighbors
+}
+
+/// Returns the `k` points nearest to `query`, nearest first.
+///
+/// # Panics
+///
+/// Panics if any point's dimension differs from the query's.
+#[must_use]
+pub fn knn<P: AsRef<[f32]>>(
+    query: &[f32],
+    points: &[P],
+    k: usize,
+    metric: Metric,
+) -> Vec<Neighbor> {
+    let query = Query::new(query, metric);
+    let mut nearest = TopQueue::new(k);
+    for (index, point) in points.iter().enumerate() {
+        push(&mut nearest, &query, index, point.as_ref());
+    }
+    neighbors(nearest)
+}
+
+/// Returns the `k` points nearest to each of `queries`, nearest first, as
+/// [`knn`] would for each.
+///
+/// # Panics
+///
+/// Panics if any vector's dimension differs from the others'.
+#[must_use]
+pub fn knn_batch<Q: AsRef<[f32]>, P: AsRef<[f32]>>(
+    queries: &[Q],
+    points: &[P],
+    k: usize,
+    metric: Metric,
+) -> Vec<Vec<Neighbor>> {
+    let queries: Vec<Query<'_>> = queries
+        .iter()
+        .map(|q| Query::new(q.as_ref(), metric))
+        .collect();
+    let mut nearest: Vec<Nearest> = queries.iter().map(|_| TopQueue::new(k)).collect();
+    for (block, chunk) in points.chunks(BLOCK).enumerate() {
+        for (query, nearest) in queries.iter().zip(&mut nearest) {
+            for (i, point) in chunk.iter().enumerate() {
+                push(nearest, query, block * BLOCK + i, point.as_ref());
+            }
+        }
+    }
+    nearest.into_iter().map(neighbors).collect()
+}
+
+/// Returns the `k` points nearest to `query`, as [`knn`] does, searching
+/// blocks of points on separate threads and merging their nearest.
+///
+/// # Panics
+///
+/// Panics if any point's dimension differs from the query's.
+#[cfg(feature = "rayon")]
+#[must_use]
+pub fn par_knn<P: AsRef<[f32]> + Sync>(
+    query: &[f32],
+    points: &[P],
+    k: usize,
+    metric: Metric,
+) -> Vec<Neighbor> {
+    let query = Query::new(query, metric);
+    let nearest = points
+        .par_chunks(BLOCK)
+        .enumerate()
+        .map(|(block, chunk)| {
+            let mut nearest = TopQueue::new(k);
+            for (i, point) in chunk.iter().enumerate() {
+                push(&mut nearest, &query, block * BLOCK + i, point.as_ref());
+            }
+            nearest
+        })
+        .reduce(
+            || TopQueue::new(k),
+            |mut a, b| {
+                a.extend(b);
+                a
+            },
+        );
+    neighbors(nearest)
+}
+
+/// Returns the `k` points nearest to each of `queries`, as [`knn_batch`]
+/// does, answering the queries on separate threads.
+///
+/// # Panics
+///
+/// Panics if any vector's dimension differs from the others'.
+#[cfg(feature = "rayon")]
+#[must_use]
+pub fn par_knn_batch<Q: AsRef<[f32]> + Sync, P: AsRef<[f32]> + Sync>(
+    queries: &[Q],
+    points: &[P],
+    k: usize,
+    metric: Metric,
+) -> Vec<Vec<Neighbor>> {
+    queries
+        .par_iter()
+        .map(|query| knn(query.as_ref(), points, k, metric))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{knn, knn_batch, Metric, Neighbor};
+    use crate::util::{rands, OrdF64};
+
+    const METRICS: [Metric; 3] = [Metric::L2, Metric::Cosine, Metric::Dot];
+
+    fn vectors(n: usize, dim: usize) -> Vec<Vec<f32>> {
+        let mut coords = rands::<f32>().map(|c| c * 2.0 - 1.0);
+        (0..n)
+            .map(|_| coords.by_ref().take(dim).collect())
+            .collect()
+    }
+
+    /// Measures the distance to every point, sorts them all, and keeps `k`.
+    fn oracle(query: &[f32], points: &[Vec<f32>], k: usize, metric: Metric) -> Vec<Neighbor> {
+        let mut all: Vec<Neighbor> = points
+            .iter()
+            .enumerate()
+            .map(|(index, p)| Neighbor {
+                index,
+                distance: metric.distance(query, p),
+            })
+            .collect();
+        all.sort_by_key(|n| (OrdF64(n.distance), n.index));
+        all.truncate(k);
+        all
+    }
+
+    #[test]
+    fn knn_matches_sorting_everything() {
+        let points = vectors(1000, 16);
+        for metric in METRICS {
+            for query in vectors(5, 16) {
+                for k in [0, 1, 10, 1000, 2000] {
+                    let expected = oracle(&query, &points, k, metric);
+                    assert_eq!(expected, knn(&query, &points, k, metric), "{metric:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn knn_batch_matches_knn() {
+        let points = vectors(1000, 8);
+        let queries = vectors(7, 8);
+        for metric in METRICS {
+            let batch = knn_batch(&queries, &points, 5, metric);
+            for (query, nearest) in queries.iter().zip(batch) {
+                assert_eq!(knn(query, &points, 5, metric), nearest);
+            }
+        }
+    }
+
+    #[test]
+    fn knn_breaks_ties_by_index() {
+        let points = [[1.0, 0.0], [0.0, 1.0], [2.0, 0.0], [0.0, 1.0], [1.0, 0.0]];
+        let index = |nearest: Vec<Neighbor>| nearest.iter().map(|n| n.index).collect::<Vec<_>>();
+        assert_eq!(
+            vec![0, 1, 3],
+            index(knn(&[0.0, 0.0], &points, 3, Metric::L2))
+        );
+        // The same direction at any length is at cosine distance 0.
+        assert_eq!(
+            vec![0, 2, 4],
+            index(knn(&[3.0, 0.0], &points, 3, Metric::Cosine))
+        );
+        assert_eq!(
+            vec![2, 0, 4],
+            index(knn(&[3.0, 0.0], &points, 3, Metric::Dot))
+        );
+    }
+
+    #[test]
+    fn metrics_measure_distance() {
+        assert!((Metric::L2.distance(&[0.0, 3.0], &[4.0, 0.0]) - 5.0).abs() < 1e-12);
+        assert!((Metric::Cosine.distance(&[1.0, 0.0], &[-2.0, 0.0]) - 2.0).abs() < 1e-12);
+        assert!((Metric::Cosine.distance(&[0.0, 0.0], &[1.0, 1.0]) - 1.0).abs() < 1e-12);
+        assert!((Metric::Dot.distance(&[1.0, 2.0], &[3.0, 4.0]) + 11.0).abs() < 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "same dimension")]
+    fn knn_rejects_mismatched_dimensions() {
+        let _ = knn(&[1.0, 2.0], &[vec![1.0]], 1, Metric::L2);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_search_matches_sequential() {
+        use super::{par_knn, par_knn_batch};
+
+        let points = vectors(5000, 8);
+        let queries = vectors(6, 8);
+        for metric in METRICS {
+            for k in [0, 1, 50, 6000] {
+                let expected = knn_batch(&queries, &points, k, metric);
+                assert_eq!(expected, par_knn_batch(&queries, &points, k, metric));
+                assert_eq!(expected[0], par_knn(&queries[0], &points, k, metric));
+            }
+        }
+    }
+}
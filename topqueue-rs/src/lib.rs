@@ -6,20 +6,68 @@
 //! custom Priority Queue, originally in Scala[^original].
 //!
 //! Rust counterparts to some of the code samples from [page 1][scala_interview_1] can be
-//! found in [util].
+//! found in [util], and [`adaptive`] picks the fastest of those and other ways
+//! to find the top items of a slice.
 //!
 //! A Rust port of the initial "naive" implementation of `TopQueue` is in [`topqueue_basic`].
 //!
 //! The final Rust version, featuring most applicable elements from the final
-//! Scala version, is in [`topqueue_final`].
+//! Scala version, is in [`topqueue_final`]. Its insertions and evictions can be
+//! watched through the hooks in [`observer`], and its underlying heap swapped
+//! for one of those in [`heap`].
+//! [`render`] draws those heaps, and each step of a push into them.
+//! With the `instrument` feature, [`instrument`] counts the work they do.
+//!
+//! [`extremes`] tracks the largest and smallest items of a stream at once,
+//! and [`trending`] ranks keys by scores that decay with age.
+//! [`quantiles`] estimates quantiles of a stream, exactly in its upper tail.
+//! [`skyline`] keeps the items no other beats on every one of several
+//! objectives, and [`knn`] finds the vectors nearest a query.
+//! [`threshold`] merges ranked lists, stopping once their top items are known.
+//! With the `async` feature, [`stream`] finds the top items of async streams.
+//!
+//! [`ffi`] exposes `TopQueue` to C, as declared in `include/topqueue.h`.
+//! The `topqueue-server` binary shares named queues between local processes.
+//! [`trace`] records what's pushed into a queue, and the `topqueue-replay`
+//! binary replays it.
 //!
 //! [^original]: The original series, "A Scala Interview", can be found [here][scala_interview_1].
 //!
 //! [scala_interview_1]: https://blog.pun.ninja/scala-interview-1
+//! [adaptive]: adaptive/index.html
 //! [topqueue_basic]: topqueue_basic/index.html
 //! [topqueue_final]: topqueue_final/index.html
+//! [extremes]: extremes/index.html
+//! [ffi]: ffi/index.html
+//! [heap]: heap/index.html
+//! [instrument]: instrument/index.html
+//! [knn]: knn/index.html
+//! [observer]: observer/index.html
+//! [quantiles]: quantiles/index.html
+//! [render]: render/index.html
+//! [skyline]: skyline/index.html
+//! [stream]: stream/index.html
+//! [threshold]: threshold/index.html
+//! [trace]: trace/index.html
+//! [trending]: trending/index.html
 //! [util]: util/index.html
 
+pub mod adaptive;
+pub mod extremes;
+pub mod ffi;
+pub mod heap;
+#[cfg(feature = "instrument")]
+pub mod instrument;
+pub mod knn;
+pub mod observer;
+pub mod quantiles;
+pub mod render;
+pub mod skyline;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod threshold;
 pub mod topqueue_basic;
 pub mod topqueue_final;
+pub mod trace;
+pub mod trending;
 pub mod util;
@@ -0,0 +1,185 @@
+//! Hooks for watching items enter and leave a [`TopQueue`].
+//!
+//! A `TopQueue` reports every `push` to its [`Observer`]: the item is either
+//! inserted, possibly evicting smaller items, or rejected. Items removed
+//! explicitly, through methods like `remove`, `retain` or `set_capacity`, are
+//! handed back to the caller and not reported, though the new capacity is.
+//!
+//! ```
+//! # use topqueue::observer::{Counter, EvictInto};
+//! # use topqueue::topqueue_final::TopQueue;
+//! let mut q = TopQueue::new(3).with_observer(EvictInto::new(TopQueue::new(2)));
+//! for n in [4, 8, 1, 9, 6, 7] {
+//!     q.push(n);
+//! }
+//! assert_eq!(vec![7, 8, 9], q.drain_sorted().collect::<Vec<_>>());
+//!
+//! // 1, 4 and 6 were evicted, and the spillover queue kept the top two.
+//! let spilled = q.into_observer().into_inner();
+//! assert_eq!(vec![4, 6], spilled.into_vec());
+//!
+//! let mut q = TopQueue::from_iter(2, [5, 3]).with_observer(Counter::default());
+//! q.push(1);
+//! q.push(4);
+//! assert_eq!(Counter { inserted: 1, evicted: 1, rejected: 1 }, *q.observer());
+//! ```
+//!
+//! [`TopQueue`]: crate::topqueue_final::TopQueue
+
+use crate::topqueue_final::TopQueue;
+
+/// Receives notifications about items pushed into a `TopQueue`.
+///
+/// Every method has an empty default, so implementors only need to override
+/// the events they care about.
+pub trait Observer<T> {
+    /// Called when `item` is about to be added to the queue.
+    fn on_insert(&mut self, _item: &T) {}
+
+    /// Called with an item that was pushed out of the queue to make room.
+    fn on_evict(&mut self, _item: T) {}
+
+    /// Called with an item that was too small, or too heavy, to be admitted.
+    fn on_reject(&mut self, _item: T) {}
+
+    /// Called when the queue's capacity is set, before any items it no longer
+    /// fits are removed.
+    fn on_set_capacity(&mut self, _capacity: usize) {}
+}
+
+/// An observer that ignores every event.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NoopObserver;
+
+impl<T> Observer<T> for NoopObserver {}
+
+/// An observer that counts each kind of event.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Counter {
+    /// Number of items inserted into the queue.
+    pub inserted: u64,
+    /// Number of items evicted from the queue by later pushes.
+    pub evicted: u64,
+    /// Number of items the queue refused.
+    pub rejected: u64,
+}
+
+impl<T> Observer<T> for Counter {
+    fn on_insert(&mut self, _item: &T) {
+        self.inserted += 1;
+    }
+
+    fn on_evict(&mut self, _item: T) {
+        self.evicted += 1;
+    }
+
+    fn on_reject(&mut self, _item: T) {
+        self.rejected += 1;
+    }
+}
+
+/// An observer that pushes evicted items into a second, lower-priority
+/// `TopQueue`.
+#[derive(Debug)]
+pub struct EvictInto<T: Ord> {
+    queue: TopQueue<T>,
+}
+
+impl<T: Ord> EvictInto<T> {
+    /// Creates an observer that forwards evictions into `queue`.
+    #[must_use]
+    pub fn new(queue: TopQueue<T>) -> Self {
+        EvictInto { queue }
+    }
+
+    /// Returns the queue receiving evicted items.
+    #[must_use]
+    pub fn queue(&self) -> &TopQueue<T> {
+        &self.queue
+    }
+
+    /// Consumes the observer, returning the queue of evicted items.
+    #[must_use]
+    pub fn into_inner(self) -> TopQueue<T> {
+        self.queue
+    }
+}
+
+impl<T: Ord> Observer<T> for EvictInto<T> {
+    fn on_evict(&mut self, item: T) {
+        self.queue.push(item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Counter, EvictInto, Observer};
+    use crate::topqueue_final::TopQueue;
+
+    /// Records every event, in order.
+    #[derive(Debug, Default)]
+    struct Log(Vec<String>);
+
+    impl Observer<u32> for Log {
+        fn on_insert(&mut self, item: &u32) {
+            self.0.push(format!("+{item}"));
+        }
+
+        fn on_evict(&mut self, item: u32) {
+            self.0.push(format!("-{item}"));
+        }
+
+        fn on_reject(&mut self, item: u32) {
+            self.0.push(format!("x{item}"));
+        }
+    }
+
+    #[test]
+    fn observer_sees_every_push() {
+        let mut q = TopQueue::new(2).with_observer(Log::default());
+        for n in [3, 5, 1, 4] {
+            q.push(n);
+        }
+        assert_eq!(vec!["+3", "+5", "x1", "-3", "+4"], q.observer().0);
+        assert_eq!(vec![4, 5], q.into_vec());
+    }
+
+    #[test]
+    fn observer_ignores_explicit_removals() {
+        let mut q = TopQueue::from_iter(4, [1, 2, 3, 4]).with_observer(Counter::default());
+        q.remove(&1);
+        q.retain(|n| *n > 2);
+        assert_eq!(vec![3], q.set_capacity(1));
+        q.clear();
+        assert_eq!(Counter::default(), *q.observer());
+    }
+
+    #[test]
+    fn counter_counts_weighted_events() {
+        let mut q = TopQueue::with_weigher(usize::MAX, 6, |n: &u32| *n as usize)
+            .with_max_item_weight(5)
+            .with_observer(Counter::default());
+        for n in [2, 3, 9, 4, 1] {
+            q.push(n);
+        }
+        // 9 is too heavy, and making room for 4 evicts both 2 and 3.
+        let counts = *q.observer();
+        assert_eq!(4, counts.inserted);
+        assert_eq!(2, counts.evicted);
+        assert_eq!(1, counts.rejected);
+
+        q.observer_mut().rejected = 0;
+        assert_eq!(0, q.observer().rejected);
+    }
+
+    #[test]
+    fn evict_into_forwards_evictions() {
+        let mut q = TopQueue::new(2).with_observer(EvictInto::new(TopQueue::new(10)));
+        for n in 1..=6 {
+            q.push(n);
+        }
+        assert_eq!(4, q.observer().queue().len());
+        let spilled = q.into_observer().into_inner();
+        assert_eq!(vec![1, 2, 3, 4], spilled.into_vec());
+    }
+}
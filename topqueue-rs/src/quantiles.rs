@@ -0,0 +1,504 @@
+//! Streaming quantile estimation, with exact answers in the upper tail.
+//!
+//! [`KllSketch`] estimates quantiles and ranks of a stream in space that
+//! grows only with the logarithm of the stream's length, using the sketch of
+//! Karnin, Lang and Liberty[^kll]. Items are kept in a stack of *compactors*:
+//! when a compactor fills up, it sorts itself and promotes every other item
+//! to the next compactor, where each item stands for twice as many originals.
+//!
+//! [`TailQuantiles`] pairs a sketch with a [`TopQueue`], so quantiles high
+//! enough to land among the queue's items, like p99 or p99.9, are exact.
+//!
+//! ```
+//! # use topqueue::quantiles::TailQuantiles;
+//! let mut latencies = TailQuantiles::new(200, 200);
+//! for ms in 1..=10_000 {
+//!     latencies.insert(ms);
+//! }
+//! let p50 = latencies.quantile(0.5).unwrap();
+//! assert!((4800..=5200).contains(&p50));
+//! // p99 is among the 200 largest, so the queue knows it exactly.
+//! assert_eq!(Some(9900), latencies.quantile(0.99));
+//! ```
+//!
+//! [^kll]: Karnin, Lang and Liberty, "Optimal Quantile Approximation in
+//!     Streams", FOCS 2016.
+//!
+//! [`TopQueue`]: crate::topqueue_final::TopQueue
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::topqueue_final::TopQueue;
+
+/// Each compactor below the top is this fraction the size of the one above.
+const SHRINK: f64 = 2.0 / 3.0;
+
+/// The smallest useful compactor size.
+const MIN_CAPACITY: usize = 2;
+
+/// A KLL sketch that estimates the quantiles and ranks of a stream.
+///
+/// A larger `k` gives more accurate estimates: rank errors are roughly
+/// *n* / `k` for a stream of *n* items, while the sketch holds around 3`k`
+/// items.
+#[derive(Debug, Clone)]
+pub struct KllSketch<T> {
+    k: usize,
+    /// `compactors[h]` holds items that each stand for `2^h` originals.
+    compactors: Vec<Vec<T>>,
+    /// The total number of items held, across all compactors.
+    size: usize,
+    max_size: usize,
+    count: u64,
+    rng: StdRng,
+}
+
+impl<T: Ord> KllSketch<T> {
+    /// Creates an empty sketch with accuracy parameter `k`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is less than 2.
+    #[must_use]
+    pub fn new(k: usize) -> Self {
+        Self::with_rng(k, StdRng::from_entropy())
+    }
+
+    /// Creates an empty sketch whose random choices are seeded by `seed`,
+    /// for reproducible results.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is less than 2.
+    #[must_use]
+    pub fn with_seed(k: usize, seed: u64) -> Self {
+        Self::with_rng(k, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(k: usize, rng: StdRng) -> Self {
+        assert!(
+            k >= MIN_CAPACITY,
+            "k must be at least {MIN_CAPACITY}, got {k}"
+        );
+        let mut sketch = KllSketch {
+            k,
+            compactors: Vec::new(),
+            size: 0,
+            max_size: 0,
+            count: 0,
+            rng,
+        };
+        sketch.grow();
+        sketch
+    }
+
+    /// Returns the accuracy parameter.
+    #[must_use]
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Returns the number of items inserted.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns true if nothing has been inserted.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the number of items the sketch is currently holding.
+    #[must_use]
+    pub fn retained(&self) -> usize {
+        self.size
+    }
+
+    /// Adds `item` to the stream.
+    pub fn insert(&mut self, item: T) {
+        self.compactors[0].push(item);
+        self.size += 1;
+        self.count += 1;
+        if self.size >= self.max_size {
+            self.compress();
+        }
+    }
+
+    /// Estimates the number of items in the stream no larger than `item`.
+    #[must_use]
+    pub fn rank(&self, item: &T) -> u64 {
+        self.weighted()
+            .filter(|(x, _)| *x <= item)
+            .map(|(_, weight)| weight)
+            .sum()
+    }
+
+    /// Estimates the `q`-quantile: the smallest item whose rank is at least
+    /// `q` times the stream's length. Returns `None` if the sketch is empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `q` is not between 0 and 1.
+    #[must_use]
+    pub fn quantile(&self, q: f64) -> Option<&T> {
+        let target = target_rank(q, self.count)?;
+        let mut items: Vec<(&T, u64)> = self.weighted().collect();
+        items.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        let mut rank = 0;
+        items
+            .into_iter()
+            .find(|&(_, weight)| {
+                rank += weight;
+                rank > target
+            })
+            .map(|(item, _)| item)
+    }
+
+    /// Folds `other` into this sketch, which then summarizes both streams.
+    pub fn merge(&mut self, other: KllSketch<T>) {
+        while self.compactors.len() < other.compactors.len() {
+            self.grow();
+        }
+        for (h, items) in other.compactors.into_iter().enumerate() {
+            self.size += items.len();
+            self.compactors[h].extend(items);
+        }
+        self.count += other.count;
+        while self.size >= self.max_size {
+            self.compress();
+        }
+    }
+
+    /// Returns every held item with the number of originals it stands for.
+    fn weighted(&self) -> impl Iterator<Item = (&T, u64)> {
+        self.compactors
+            .iter()
+            .enumerate()
+            .flat_map(|(h, items)| items.iter().map(move |item| (item, 1 << h)))
+    }
+
+    /// Returns how many items compactor `h` may hold before compacting.
+    fn capacity(&self, h: usize) -> usize {
+        let depth = self.compactors.len() - h - 1;
+        // Both conversions are of small, non-negative values.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        #[allow(clippy::cast_precision_loss, clippy::cast_possible_wrap)]
+        let capacity = (self.k as f64 * SHRINK.powi(depth as i32)).ceil() as usize;
+        capacity.max(MIN_CAPACITY)
+    }
+
+    fn grow(&mut self) {
+        self.compactors.push(Vec::new());
+        self.max_size = (0..self.compactors.len()).map(|h| self.capacity(h)).sum();
+    }
+
+    /// Compacts the lowest full compactor into the one above it.
+    fn compress(&mut self) {
+        for h in 0..self.compactors.len() {
+            if self.compactors[h].len() >= self.capacity(h) {
+                if h + 1 == self.compactors.len() {
+                    self.grow();
+                }
+                let before = self.compactors[h].len();
+                let promoted = self.compact(h);
+                self.size -= before - self.compactors[h].len() - promoted.len();
+                self.compactors[h + 1].extend(promoted);
+                return;
+            }
+        }
+    }
+
+    /// Sorts compactor `h` and returns every other item, starting with the
+    /// first or second at random. With an odd number of items, the smallest
+    /// stays behind.
+    fn compact(&mut self, h: usize) -> Vec<T> {
+        let items = &mut self.compactors[h];
+        items.sort_unstable();
+        let keep_first = self.rng.gen::<bool>();
+        let start = items.len() % 2;
+        let mut promoted = Vec::with_capacity(items.len() / 2);
+        for (i, item) in items.drain(start..).enumerate() {
+            if (i % 2 == 0) == keep_first {
+                promoted.push(item);
+            }
+        }
+        promoted
+    }
+}
+
+/// Quantiles and ranks from a [`KllSketch`], made exact in the upper tail by
+/// a [`TopQueue`] of the largest items.
+///
+/// A quantile or rank is exact whenever it falls among the queue's items,
+/// and estimated by the sketch otherwise.
+#[derive(Debug)]
+pub struct TailQuantiles<T: Ord> {
+    sketch: KllSketch<T>,
+    top: TopQueue<T>,
+}
+
+impl<T: Ord + Clone> TailQuantiles<T> {
+    /// Creates a tracker with a sketch of accuracy `k` and an exact queue of
+    /// the `tail` largest items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is less than 2.
+    #[must_use]
+    pub fn new(k: usize, tail: usize) -> Self {
+        TailQuantiles {
+            sketch: KllSketch::new(k),
+            top: TopQueue::new(tail),
+        }
+    }
+
+    /// Returns the sketch estimating the rest of the stream.
+    #[must_use]
+    pub fn sketch(&self) -> &KllSketch<T> {
+        &self.sketch
+    }
+
+    /// Returns the number of items inserted.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.sketch.count()
+    }
+
+    /// Adds `item` to the stream.
+    pub fn insert(&mut self, item: T) {
+        self.top.push(item.clone());
+        self.sketch.insert(item);
+    }
+
+    /// Returns true if the `q`-quantile will be answered exactly.
+    #[must_use]
+    pub fn is_exact(&self, q: f64) -> bool {
+        self.tail_index(q).is_some()
+    }
+
+    /// Returns the `q`-quantile: exactly if it's among the queue's items,
+    /// and as estimated by the sketch otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `q` is not between 0 and 1.
+    #[must_use]
+    pub fn quantile(&self, q: f64) -> Option<T> {
+        match self.tail_index(q) {
+            Some(from_top) => self.top.page(from_top, 1).pop().cloned(),
+            None => self.sketch.quantile(q).cloned(),
+        }
+    }
+
+    /// Returns the number of items in the stream no larger than `item`:
+    /// exactly if `item` is at least the smallest item in the queue, and as
+    /// estimated by the sketch otherwise.
+    #[must_use]
+    pub fn rank(&self, item: &T) -> u64 {
+        let every_item_kept = self.top.len() as u64 == self.count();
+        if every_item_kept || self.top.peek_min().is_some_and(|min| min <= item) {
+            let larger = self.top.page(0, self.top.len());
+            let larger = larger.iter().take_while(|x| **x > item).count();
+            self.count() - larger as u64
+        } else {
+            self.sketch.rank(item)
+        }
+    }
+
+    /// Folds `other` into this tracker, which then summarizes both streams.
+    ///
+    /// The combined queue is only exact as deep as the shallower of the two
+    /// tails, so it shrinks to that unless a tracker kept its whole stream.
+    pub fn merge(&mut self, mut other: TailQuantiles<T>) {
+        let depth = |tq: &TailQuantiles<T>| {
+            let every_item_kept = tq.top.len() as u64 == tq.count();
+            if every_item_kept {
+                usize::MAX
+            } else {
+                tq.top.capacity()
+            }
+        };
+        let depth = depth(self).min(depth(&other));
+        for item in other.top.drain_sorted() {
+            self.top.push(item);
+        }
+        if depth < self.top.capacity() {
+            self.top.set_capacity(depth);
+        }
+        self.sketch.merge(other.sketch);
+    }
+
+    /// Returns the position, counting from the largest, of the `q`-quantile
+    /// if the queue holds it.
+    fn tail_index(&self, q: f64) -> Option<usize> {
+        let target = target_rank(q, self.count())?;
+        let from_top = usize::try_from(self.count() - 1 - target).ok()?;
+        (from_top < self.top.len()).then_some(from_top)
+    }
+}
+
+/// Returns the 0-based position in sorted order of the `q`-quantile of
+/// `count` items, or `None` if there are none.
+fn target_rank(q: f64, count: u64) -> Option<u64> {
+    assert!(
+        (0.0..=1.0).contains(&q),
+        "quantile must be between 0 and 1, got {q}"
+    );
+    // The product is within 0..=count, which fits an f64 closely enough.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    let rank = (q * count as f64).ceil() as u64;
+    (count > 0).then(|| rank.saturating_sub(1).min(count - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KllSketch, TailQuantiles};
+    use crate::util::rands;
+
+    /// Asserts `actual` is within `tolerance` of `expected`, as a fraction of `n`.
+    #[allow(clippy::cast_precision_loss)]
+    fn assert_near(expected: u64, actual: u64, n: u64, tolerance: f64) {
+        let error = expected.abs_diff(actual) as f64 / n as f64;
+        assert!(error <= tolerance, "expected ~{expected}, got {actual}");
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn kll_estimates_ranks_and_quantiles() {
+        let mut sketch = KllSketch::with_seed(200, 7);
+        let nums: Vec<u32> = rands::<u32>().take(100_000).map(|n| n % 100_000).collect();
+        for &n in &nums {
+            sketch.insert(n);
+        }
+        assert_eq!(100_000, sketch.count());
+        assert!(sketch.retained() < 1000, "{} retained", sketch.retained());
+
+        let mut sorted = nums;
+        sorted.sort_unstable();
+        for q in [0.01, 0.25, 0.5, 0.75, 0.99] {
+            let estimate = *sketch.quantile(q).unwrap();
+            let true_rank = sorted.partition_point(|&n| n <= estimate) as u64;
+            assert_near((q * 100_000.0) as u64, true_rank, 100_000, 0.02);
+        }
+        for x in [0, 10_000, 50_000, 99_999] {
+            let true_rank = sorted.partition_point(|&n| n <= x) as u64;
+            assert_near(true_rank, sketch.rank(&x), 100_000, 0.02);
+        }
+    }
+
+    #[test]
+    fn kll_is_exact_while_small() {
+        let mut sketch = KllSketch::with_seed(100, 1);
+        assert_eq!(None, sketch.quantile(0.5));
+        for n in [5, 1, 4, 2, 3] {
+            sketch.insert(n);
+        }
+        assert_eq!(Some(&1), sketch.quantile(0.0));
+        assert_eq!(Some(&3), sketch.quantile(0.5));
+        assert_eq!(Some(&5), sketch.quantile(1.0));
+        assert_eq!(2, sketch.rank(&2));
+    }
+
+    #[test]
+    fn kll_merges_streams() {
+        let mut evens = KllSketch::with_seed(200, 2);
+        let mut odds = KllSketch::with_seed(200, 3);
+        for n in 0..50_000_u32 {
+            evens.insert(2 * n);
+            odds.insert(2 * n + 1);
+        }
+        evens.merge(odds);
+        assert_eq!(100_000, evens.count());
+        assert!(evens.retained() < 1000);
+        assert_near(50_000, evens.rank(&49_999), 100_000, 0.02);
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn tail_quantiles_are_exact_in_the_tail() {
+        let nums: Vec<u32> = rands().take(50_000).collect();
+        let mut tq = TailQuantiles::new(100, 600);
+        for &n in &nums {
+            tq.insert(n);
+        }
+        let mut sorted = nums;
+        sorted.sort_unstable();
+
+        for q in [0.99, 0.995, 0.999, 1.0] {
+            assert!(tq.is_exact(q));
+            let i = ((q * 50_000.0_f64).ceil() as usize).max(1) - 1;
+            assert_eq!(Some(sorted[i]), tq.quantile(q));
+        }
+        assert!(!tq.is_exact(0.5));
+
+        let x = sorted[49_800];
+        assert_eq!(49_801, tq.rank(&x));
+        assert_eq!(50_000, tq.rank(&u32::MAX));
+    }
+
+    #[test]
+    fn tail_quantiles_merge() {
+        let mut low = TailQuantiles::new(100, 10);
+        let mut high = TailQuantiles::new(100, 10);
+        for n in 0..1000 {
+            low.insert(n);
+            high.insert(n + 1000);
+        }
+        low.merge(high);
+        assert_eq!(2000, low.count());
+        assert_eq!(Some(1999), low.quantile(1.0));
+        assert!(!low.is_exact(0.995));
+        assert_eq!(Some(1991), low.quantile(0.996));
+        assert_eq!(1991, low.rank(&1990));
+    }
+
+    #[test]
+    fn tail_quantiles_merge_keeps_the_shallower_tail() {
+        let mut deep = TailQuantiles::new(100, 10);
+        let mut shallow = TailQuantiles::new(100, 2);
+        for n in 0..100 {
+            deep.insert(n);
+            shallow.insert(n + 1000);
+        }
+        deep.merge(shallow);
+        assert_eq!(200, deep.count());
+        // Only the top two of the combined streams are known exactly.
+        assert!(deep.is_exact(0.995));
+        assert_eq!(Some(1098), deep.quantile(0.995));
+        assert!(!deep.is_exact(0.99));
+        assert_ne!(Some(99), deep.quantile(0.99));
+
+        // A tracker that kept its whole stream doesn't limit the other.
+        let mut big = TailQuantiles::new(100, 10);
+        let mut small = TailQuantiles::new(100, 3);
+        for n in 0..100 {
+            big.insert(n);
+        }
+        small.insert(1000);
+        big.merge(small);
+        assert_eq!(Some(1000), big.quantile(1.0));
+        assert!(big.is_exact(0.95));
+        assert_eq!(Some(95), big.quantile(0.95));
+    }
+
+    #[test]
+    fn tail_quantiles_without_a_tail_use_the_sketch() {
+        let mut tq = TailQuantiles::new(8, 0);
+        assert_eq!(0, tq.rank(&1));
+        tq.insert(1);
+        assert_eq!(1, tq.rank(&1));
+        assert_eq!(0, tq.rank(&0));
+        assert!(!tq.is_exact(1.0));
+        assert_eq!(Some(1), tq.quantile(1.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "quantile must be between 0 and 1")]
+    fn quantile_rejects_out_of_range() {
+        let _ = KllSketch::<u8>::new(8).quantile(1.5);
+    }
+}
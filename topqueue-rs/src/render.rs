@@ -0,0 +1,502 @@
+//! Pictures of the heap inside a `TopQueue`, for seeing how it works.
+//!
+//! Both [`topqueue_basic::TopQueue`] and the default
+//! [`topqueue_final::TopQueue`] keep their items in a
+//! `BinaryHeap<Reverse<T>>`: a complete binary tree stored in an array, where
+//! the children of index `i` are at `2i + 1` and `2i + 2`, and every item is
+//! no larger than its children. The smallest item, the next to be evicted, is
+//! therefore always at the root.
+//!
+//! A [`HeapView`] captures that array and draws it as Graphviz DOT, as an
+//! indented tree, or as the array itself. `BinaryHeap` doesn't promise where
+//! each item lands, only that the array is a heap:
+//!
+//! ```
+//! # use topqueue::render::HeapView;
+//! # use topqueue::topqueue_final::TopQueue;
+//! let q = TopQueue::from_iter(5, [8, 3, 9, 1, 7, 4]);
+//! let view = HeapView::of(&q);
+//! let items = view.items();
+//! assert!((1..items.len()).all(|i| items[(i - 1) / 2] <= items[i]));
+//! // Drawn as, for instance:
+//! // 3
+//! // ├── 4
+//! // │   ├── 8
+//! // │   └── 7
+//! // └── 9
+//! assert!(view.to_ascii_tree().starts_with("3\n├── "));
+//! assert!(view.to_array().starts_with("index: 0 1 2 3 4\nitem:  3 "));
+//! ```
+//!
+//! [`push_frames`] and [`push_frames_basic`] push an item and return a
+//! [`Frame`] for each step it took: the item being appended or evicted, and
+//! each swap as it sifted to its place. The sifts are the textbook ones,
+//! which `BinaryHeap` follows today without promising to, so the last frame
+//! may order the items differently from the queue. Each call starts from the
+//! queue's own heap, so its first frame may not match the previous call's
+//! last.
+//!
+//! [`topqueue_basic::TopQueue`]: crate::topqueue_basic::TopQueue
+//! [`topqueue_final::TopQueue`]: crate::topqueue_final::TopQueue
+
+use std::fmt::{Display, Write as _};
+
+use crate::observer::Observer;
+use crate::topqueue_basic;
+use crate::topqueue_final::TopQueue;
+
+/// A heap's items in array order, where index `i` is the parent of indices
+/// `2i + 1` and `2i + 2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapView<T> {
+    items: Vec<T>,
+}
+
+impl HeapView<i32> {
+    /// Captures the heap inside a [`topqueue_basic::TopQueue`].
+    #[must_use]
+    pub fn of_basic(queue: &topqueue_basic::TopQueue) -> Self {
+        let items = queue.heap_items().collect();
+        HeapView { items }
+    }
+}
+
+impl<T: Ord + Clone> HeapView<T> {
+    /// Captures the heap inside a [`TopQueue`].
+    #[must_use]
+    pub fn of<O: Observer<T>>(queue: &TopQueue<T, O>) -> Self {
+        let items = queue
+            .heap()
+            .as_slice()
+            .iter()
+            .map(|r| r.0.clone())
+            .collect();
+        HeapView { items }
+    }
+}
+
+impl<T> HeapView<T> {
+    /// Returns the items in array order.
+    #[must_use]
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+}
+
+impl<T: Display> HeapView<T> {
+    /// Draws the heap as a Graphviz `digraph`, one node per index.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        dot(&self.items, &[])
+    }
+
+    /// Draws the heap as an indented tree, the root first.
+    #[must_use]
+    pub fn to_ascii_tree(&self) -> String {
+        ascii_tree(&self.items, &[])
+    }
+
+    /// Draws the heap as its array, each item under its index.
+    #[must_use]
+    pub fn to_array(&self) -> String {
+        array(&self.items, &[])
+    }
+}
+
+/// One step of a push, as recorded by [`push_frames`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step<T> {
+    /// The new item was appended to the heap at `index`.
+    Append {
+        /// Where the item landed.
+        index: usize,
+    },
+    /// The smallest item was evicted from the root to make room.
+    ///
+    /// `topqueue_basic` refills the root with the heap's last item before
+    /// pushing the new one; `topqueue_final` refills it with the new item.
+    Evict(T),
+    /// The items at `parent` and `child` traded places.
+    Swap {
+        /// The upper of the two indices.
+        parent: usize,
+        /// The lower of the two indices.
+        child: usize,
+    },
+    /// The new item was too small to keep, and the heap is unchanged.
+    Reject(T),
+}
+
+/// A [`Step`] of a push and the heap just after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame<T> {
+    /// What happened.
+    pub step: Step<T>,
+    /// The heap once it had happened.
+    pub heap: HeapView<T>,
+}
+
+impl<T> Frame<T> {
+    /// Returns the indices the step touched.
+    #[must_use]
+    pub fn touched(&self) -> Vec<usize> {
+        match self.step {
+            Step::Append { index } => vec![index],
+            Step::Evict(_) => vec![0],
+            Step::Swap { parent, child } => vec![parent, child],
+            Step::Reject(_) => vec![],
+        }
+    }
+}
+
+impl<T: Display> Frame<T> {
+    /// Draws the heap as [`HeapView::to_dot`] does, with the touched nodes
+    /// filled in.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        dot(&self.heap.items, &self.touched())
+    }
+
+    /// Draws the heap as [`HeapView::to_ascii_tree`] does, with the touched
+    /// items starred.
+    #[must_use]
+    pub fn to_ascii_tree(&self) -> String {
+        ascii_tree(&self.heap.items, &self.touched())
+    }
+
+    /// Draws the heap as [`HeapView::to_array`] does, with a line of carets
+    /// under the touched items.
+    #[must_use]
+    pub fn to_array(&self) -> String {
+        array(&self.heap.items, &self.touched())
+    }
+}
+
+/// Pushes `n` into `queue`, returning the steps it took.
+pub fn push_frames_basic(queue: &mut topqueue_basic::TopQueue, n: i32) -> Vec<Frame<i32>> {
+    let mut sim = Simulation::new(HeapView::of_basic(queue).items);
+    if queue.len() < queue.capacity() {
+        sim.append(n);
+    } else if sim.heap.first().is_some_and(|&min| n > min) {
+        sim.pop_min();
+        sim.append(n);
+    } else {
+        sim.record(Step::Reject(n));
+    }
+    queue.push(n);
+    sim.frames
+}
+
+/// Pushes `item` into `queue`, returning the steps it took.
+///
+/// # Panics
+///
+/// Panics if `queue` was created with a weigher, since its evictions depend
+/// on weights that the frames don't show.
+pub fn push_frames<T, O>(queue: &mut TopQueue<T, O>, item: T) -> Vec<Frame<T>>
+where
+    T: Ord + Clone,
+    O: Observer<T>,
+{
+    assert!(
+        queue.weight_budget().is_none(),
+        "can't show the pushes of a weighted queue"
+    );
+    let mut sim = Simulation::new(HeapView::of(queue).items);
+    if queue.len() < queue.capacity() {
+        sim.append(item.clone());
+    } else if sim.heap.first().is_some_and(|min| &item >= min) {
+        sim.replace_min(item.clone());
+    } else {
+        sim.record(Step::Reject(item.clone()));
+    }
+    queue.push(item);
+    sim.frames
+}
+
+/// Replays a push on a copy of the heap with the textbook sifts, and records
+/// each step.
+///
+/// These are the sifts `BinaryHeap` uses today, but it doesn't promise to, so
+/// the last frame is a valid heap of the same items as the queue, not
+/// necessarily in the same order.
+struct Simulation<T> {
+    heap: Vec<T>,
+    frames: Vec<Frame<T>>,
+}
+
+impl<T: Ord + Clone> Simulation<T> {
+    fn new(heap: Vec<T>) -> Self {
+        Simulation {
+            heap,
+            frames: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, step: Step<T>) {
+        let heap = HeapView {
+            items: self.heap.clone(),
+        };
+        self.frames.push(Frame { step, heap });
+    }
+
+    fn swap(&mut self, parent: usize, child: usize) {
+        self.heap.swap(parent, child);
+        self.record(Step::Swap { parent, child });
+    }
+
+    /// Appends `item` and sifts it up.
+    fn append(&mut self, item: T) {
+        self.heap.push(item);
+        let index = self.heap.len() - 1;
+        self.record(Step::Append { index });
+        self.sift_up(index);
+    }
+
+    /// Evicts the root: the last item fills it, sinks all the way to the
+    /// bottom along the smaller children, then rises back up.
+    fn pop_min(&mut self) {
+        let last = self.heap.len() - 1;
+        self.heap.swap(0, last);
+        let Some(evicted) = self.heap.pop() else {
+            return;
+        };
+        self.record(Step::Evict(evicted));
+
+        let end = self.heap.len();
+        let mut pos = 0;
+        let mut child = 1;
+        while child + 1 < end {
+            child += usize::from(self.heap[child + 1] <= self.heap[child]);
+            self.swap(pos, child);
+            pos = child;
+            child = 2 * pos + 1;
+        }
+        if child + 1 == end {
+            self.swap(pos, child);
+            pos = child;
+        }
+        self.sift_up(pos);
+    }
+
+    /// Replaces the root with `item`, which sinks until no child is
+    /// smaller.
+    fn replace_min(&mut self, item: T) {
+        let evicted = std::mem::replace(&mut self.heap[0], item);
+        self.record(Step::Evict(evicted));
+
+        let end = self.heap.len();
+        let mut pos = 0;
+        let mut child = 1;
+        while child + 1 < end {
+            child += usize::from(self.heap[child + 1] <= self.heap[child]);
+            if self.heap[pos] <= self.heap[child] {
+                return;
+            }
+            self.swap(pos, child);
+            pos = child;
+            child = 2 * pos + 1;
+        }
+        if child + 1 == end && self.heap[pos] > self.heap[child] {
+            self.swap(pos, child);
+        }
+    }
+
+    fn sift_up(&mut self, mut pos: usize) {
+        while pos > 0 {
+            let parent = (pos - 1) / 2;
+            if self.heap[pos] >= self.heap[parent] {
+                break;
+            }
+            self.swap(parent, pos);
+            pos = parent;
+        }
+    }
+}
+
+fn dot<T: Display>(items: &[T], touched: &[usize]) -> String {
+    let mut out = String::from("digraph heap {\n    node [shape=circle];\n");
+    for (i, item) in items.iter().enumerate() {
+        let label = format!("{:?}", item.to_string());
+        let fill = if touched.contains(&i) {
+            ", style=filled, fillcolor=lightblue"
+        } else {
+            ""
+        };
+        let _ = writeln!(out, "    n{i} [label={label}, xlabel=\"{i}\"{fill}];");
+    }
+    for i in 1..items.len() {
+        let _ = writeln!(out, "    n{} -> n{i};", (i - 1) / 2);
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn ascii_tree<T: Display>(items: &[T], touched: &[usize]) -> String {
+    fn node<T: Display>(out: &mut String, items: &[T], touched: &[usize], i: usize, prefix: &str) {
+        for (child, last) in [(2 * i + 1, false), (2 * i + 2, true)] {
+            let Some(item) = items.get(child) else {
+                return;
+            };
+            // The left child is the last one drawn if there's no right child.
+            let last = last || child + 1 == items.len();
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            let star = if touched.contains(&child) { " *" } else { "" };
+            let _ = writeln!(out, "{prefix}{branch}{item}{star}");
+            node(out, items, touched, child, &format!("{prefix}{indent}"));
+        }
+    }
+
+    let mut out = String::new();
+    if let Some(root) = items.first() {
+        let star = if touched.contains(&0) { " *" } else { "" };
+        let _ = writeln!(out, "{root}{star}");
+        node(&mut out, items, touched, 0, "");
+    }
+    out
+}
+
+fn array<T: Display>(items: &[T], touched: &[usize]) -> String {
+    let labels: Vec<String> = items.iter().map(ToString::to_string).collect();
+    let widths: Vec<usize> = labels
+        .iter()
+        .enumerate()
+        .map(|(i, label)| label.chars().count().max(i.to_string().len()))
+        .collect();
+
+    let row = |title: &str, cells: &mut dyn Iterator<Item = String>| {
+        let mut line = String::from(title);
+        for (cell, width) in cells.zip(&widths) {
+            let _ = write!(line, " {cell:<width$}");
+        }
+        line.truncate(line.trim_end().len());
+        line.push('\n');
+        line
+    };
+    let mut out = row("index:", &mut (0..items.len()).map(|i| i.to_string()));
+    out += &row("item: ", &mut labels.iter().cloned());
+    if !touched.is_empty() {
+        let marks =
+            (0..items.len()).map(|i| if touched.contains(&i) { "^" } else { "" }.to_string());
+        out += &row("      ", &mut marks.into_iter());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{push_frames, push_frames_basic, Frame, HeapView, Simulation, Step};
+    use crate::topqueue_basic;
+    use crate::topqueue_final::TopQueue;
+    use crate::util::rands;
+
+    /// Asserts `heap` is a valid heap of the same items as `expected`.
+    fn assert_heap_of(expected: &[i32], heap: &HeapView<i32>) {
+        let items = heap.items();
+        assert!((1..items.len()).all(|i| items[(i - 1) / 2] <= items[i]));
+        let sorted = |items: &[i32]| {
+            let mut items = items.to_vec();
+            items.sort_unstable();
+            items
+        };
+        assert_eq!(sorted(expected), sorted(items));
+    }
+
+    #[test]
+    fn frames_replay_basic_pushes() {
+        let mut q = topqueue_basic::TopQueue::new(20);
+        for n in rands::<i32>().take(2000).map(|n| n % 100) {
+            let frames = push_frames_basic(&mut q, n);
+            let last = frames.last().unwrap();
+            assert_heap_of(HeapView::of_basic(&q).items(), &last.heap);
+        }
+    }
+
+    #[test]
+    fn frames_replay_final_pushes() {
+        let mut q = TopQueue::new(20);
+        for n in rands::<i32>().take(2000).map(|n| n % 100) {
+            let frames = push_frames(&mut q, n);
+            let last = frames.last().unwrap();
+            assert_heap_of(HeapView::of(&q).items(), &last.heap);
+        }
+    }
+
+    #[test]
+    fn frames_show_sift_paths_and_evictions() {
+        let steps =
+            |frames: Vec<Frame<i32>>| frames.into_iter().map(|f| f.step).collect::<Vec<_>>();
+
+        // 8 evicts 5 from the root, then sinks below the smaller child, 7.
+        let mut sim = Simulation::new(vec![5, 7, 9]);
+        sim.replace_min(8);
+        assert_eq!(
+            vec![
+                Step::Evict(5),
+                Step::Swap {
+                    parent: 0,
+                    child: 1
+                }
+            ],
+            steps(sim.frames)
+        );
+        let mut q = TopQueue::from_iter(3, [5, 7, 9]);
+        assert_eq!(vec![Step::Reject(4)], steps(push_frames(&mut q, 4)));
+
+        let mut sim = Simulation::new(vec![5, 7, 9]);
+        sim.append(1);
+        assert_eq!(
+            vec![
+                Step::Append { index: 3 },
+                Step::Swap {
+                    parent: 1,
+                    child: 3
+                },
+                Step::Swap {
+                    parent: 0,
+                    child: 1
+                }
+            ],
+            steps(sim.frames)
+        );
+    }
+
+    #[test]
+    fn render_formats() {
+        let q = TopQueue::from_iter(4, [30, 10, 20, 40]);
+        assert_heap_of(&[10, 20, 30, 40], &HeapView::of(&q));
+
+        let view = HeapView {
+            items: vec![10, 30, 20, 40],
+        };
+        assert_eq!(
+            "digraph heap {\n    node [shape=circle];\n    \
+             n0 [label=\"10\", xlabel=\"0\"];\n    n1 [label=\"30\", xlabel=\"1\"];\n    \
+             n2 [label=\"20\", xlabel=\"2\"];\n    n3 [label=\"40\", xlabel=\"3\"];\n    \
+             n0 -> n1;\n    n0 -> n2;\n    n1 -> n3;\n}\n",
+            view.to_dot()
+        );
+        assert_eq!("10\n├── 30\n│   └── 40\n└── 20\n", view.to_ascii_tree());
+
+        let mut sim = Simulation::new(view.items);
+        sim.replace_min(25);
+        let frames = sim.frames;
+        let evict = &frames[0];
+        assert_eq!("25 *\n├── 30\n│   └── 40\n└── 20\n", evict.to_ascii_tree());
+        assert_eq!(
+            "index: 0  1  2  3\nitem:  25 30 20 40\n       ^\n",
+            evict.to_array()
+        );
+        assert!(frames[1]
+            .to_dot()
+            .contains("n2 [label=\"25\", xlabel=\"2\", style=filled"));
+
+        let empty = HeapView::of(&TopQueue::<u8>::new(1));
+        assert_eq!("", empty.to_ascii_tree());
+        assert_eq!("index:\nitem:\n", empty.to_array());
+    }
+}
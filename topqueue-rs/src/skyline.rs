@@ -0,0 +1,455 @@
+//! A queue of the items not beaten on every objective at once.
+//!
+//! Ranking by a single key can't express trade-offs. When configurations are
+//! judged on both latency and memory, the interesting ones are those that no
+//! other configuration beats on both: the *skyline*, or Pareto front. One
+//! item *dominates* another if it's at least as good on every objective and
+//! strictly better on one. A [`SkylineQueue`] keeps the items no other pushed
+//! item dominates, updating them as each new item arrives.
+//!
+//! ```
+//! # use topqueue::skyline::{Goal, SkylineQueue};
+//! // (name, latency in ms, memory in MB)
+//! let mut configs = SkylineQueue::new(&[Goal::Min, Goal::Min], |c: &(&str, f64, f64)| {
+//!     vec![c.1, c.2]
+//! });
+//! configs.push(("small", 40.0, 100.0));
+//! configs.push(("large", 10.0, 900.0));
+//! configs.push(("bloated", 45.0, 800.0));
+//! configs.push(("balanced", 20.0, 300.0));
+//!
+//! // "bloated" is beaten on both by "balanced", so it never joined.
+//! let names: Vec<_> = configs.iter().map(|c| c.0).collect();
+//! assert_eq!(vec!["small", "large", "balanced"], names);
+//! ```
+//!
+//! A front can grow as large as its input. A queue given a capacity keeps it
+//! bounded by evicting the item in the most crowded part of the front, as
+//! NSGA-II[^nsga] does, which keeps the best item on each objective and
+//! spreads the rest along the front. The result approximates the front: an
+//! item dominated only by evicted items may still be admitted later.
+//!
+//! Items enter and leave the queue with the same [`Observer`] events as a
+//! `TopQueue`: rejected if dominated, and evicted if dominated by a new item
+//! or crowded out.
+//!
+//! [^nsga]: Deb, Pratap, Agarwal and Meyarivan, "A Fast and Elitist
+//!     Multiobjective Genetic Algorithm: NSGA-II", IEEE Transactions on
+//!     Evolutionary Computation, 2002.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::observer::{NoopObserver, Observer};
+
+/// Which direction of an objective is better.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    /// Smaller values are better.
+    Min,
+    /// Larger values are better.
+    Max,
+}
+
+impl Goal {
+    /// Compares `a` to `b`, where `Greater` means `a` is better.
+    fn compare(self, a: f64, b: f64) -> Ordering {
+        let ordering = a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+        match self {
+            Goal::Min => ordering.reverse(),
+            Goal::Max => ordering,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry<T> {
+    item: T,
+    keys: Vec<f64>,
+}
+
+/// A collection that retains the items not dominated by any other item
+/// inserted into it.
+///
+/// Each item is scored on several objectives by a user-supplied function,
+/// and each objective has a [`Goal`].
+#[derive(Debug, Clone)]
+pub struct SkylineQueue<T, O: Observer<T> = NoopObserver> {
+    goals: Vec<Goal>,
+    keys: Scorer<T>,
+    capacity: Option<usize>,
+    entries: Vec<Entry<T>>,
+    observer: O,
+}
+
+impl<T> SkylineQueue<T> {
+    /// Creates an empty queue, scoring items with `keys` on objectives with
+    /// the given `goals`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no goals.
+    #[must_use]
+    pub fn new<K>(goals: &[Goal], keys: K) -> Self
+    where
+        K: Fn(&T) -> Vec<f64> + Send + Sync + 'static,
+    {
+        assert!(!goals.is_empty(), "a skyline needs at least one objective");
+        SkylineQueue {
+            goals: goals.to_vec(),
+            keys: Scorer(Arc::new(keys)),
+            capacity: None,
+            entries: Vec::new(),
+            observer: NoopObserver,
+        }
+    }
+}
+
+impl<T, O: Observer<T>> SkylineQueue<T, O> {
+    /// Replaces the queue's observer, keeping its contents and settings.
+    #[must_use]
+    pub fn with_observer<O2: Observer<T>>(self, observer: O2) -> SkylineQueue<T, O2> {
+        SkylineQueue {
+            goals: self.goals,
+            keys: self.keys,
+            capacity: self.capacity,
+            entries: self.entries,
+            observer,
+        }
+    }
+
+    /// Bounds the queue to `capacity` items, evicting the most crowded items
+    /// until it fits.
+    ///
+    /// Crowding distances are recomputed after each eviction, so shrinking a
+    /// queue of *n* items this way takes *O*(*n*² log *n*). Bounding the queue
+    /// before pushing avoids that: a push evicts at most one item for
+    /// crowding, so computes the distances at most once.
+    #[must_use]
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self.evict_crowded();
+        self
+    }
+
+    /// Returns the queue's observer.
+    #[must_use]
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Returns the queue's capacity, if it has one.
+    #[must_use]
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// Returns the objectives' goals.
+    #[must_use]
+    pub fn goals(&self) -> &[Goal] {
+        &self.goals
+    }
+
+    /// Returns the number of items in the queue.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the queue is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over the items, in the order they were inserted.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &T> {
+        self.entries.iter().map(|entry| &entry.item)
+    }
+
+    /// Returns the items, in the order they were inserted.
+    #[must_use]
+    pub fn into_vec(self) -> Vec<T> {
+        self.entries.into_iter().map(|entry| entry.item).collect()
+    }
+
+    /// Returns true if `a` dominates `b`: it's at least as good on every
+    /// objective, and better on at least one.
+    pub fn dominates(&self, a: &T, b: &T) -> bool {
+        dominates(&self.goals, &self.score(a), &self.score(b))
+    }
+
+    /// Returns each item's crowding distance, in the order of
+    /// [`iter`](SkylineQueue::iter).
+    ///
+    /// An item's crowding distance sums, over the objectives, the gap
+    /// between its neighbours on either side, relative to the range of that
+    /// objective. The best and worst items on any objective are infinitely
+    /// far from the crowd.
+    #[must_use]
+    pub fn crowding_distances(&self) -> Vec<f64> {
+        let n = self.entries.len();
+        let mut distances = vec![0.0; n];
+        let mut order: Vec<usize> = (0..n).collect();
+        for objective in 0..self.goals.len() {
+            let key = |i: usize| self.entries[i].keys[objective];
+            order.sort_by(|&a, &b| key(a).total_cmp(&key(b)));
+            let (Some(&low), Some(&high)) = (order.first(), order.last()) else {
+                break;
+            };
+            distances[low] = f64::INFINITY;
+            distances[high] = f64::INFINITY;
+            let range = key(high) - key(low);
+            if range > 0.0 {
+                for window in order.windows(3) {
+                    distances[window[1]] += (key(window[2]) - key(window[0])) / range;
+                }
+            }
+        }
+        distances
+    }
+
+    /// Offers `item` to the queue.
+    ///
+    /// If an item in the queue dominates it, it's rejected. Otherwise it's
+    /// inserted, and every item it dominates is evicted. Then, if the queue
+    /// is over capacity, the most crowded item is evicted, which may be the
+    /// new item itself. In that case the observer sees the item inserted and
+    /// then evicted.
+    ///
+    /// Items with equal scores don't dominate each other, so are all kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the item's scores don't match the goals in number, or any
+    /// is NaN.
+    pub fn push(&mut self, item: T) {
+        let keys = self.score(&item);
+        if self
+            .entries
+            .iter()
+            .any(|entry| dominates(&self.goals, &entry.keys, &keys))
+        {
+            self.observer.on_reject(item);
+            return;
+        }
+
+        self.observer.on_insert(&item);
+        let mut i = 0;
+        while i < self.entries.len() {
+            if dominates(&self.goals, &keys, &self.entries[i].keys) {
+                let evicted = self.entries.remove(i);
+                self.observer.on_evict(evicted.item);
+            } else {
+                i += 1;
+            }
+        }
+        self.entries.push(Entry { item, keys });
+        self.evict_crowded();
+    }
+
+    fn score(&self, item: &T) -> Vec<f64> {
+        let keys = (self.keys.0)(item);
+        assert_eq!(
+            self.goals.len(),
+            keys.len(),
+            "an item must have a score for each objective"
+        );
+        assert!(
+            !keys.iter().any(|k| k.is_nan()),
+            "skyline scores must not be NaN"
+        );
+        keys
+    }
+
+    fn evict_crowded(&mut self) {
+        let Some(capacity) = self.capacity else {
+            return;
+        };
+        while self.entries.len() > capacity {
+            let distances = self.crowding_distances();
+            let most_crowded = (0..distances.len())
+                .min_by(|&a, &b| distances[a].total_cmp(&distances[b]))
+                .unwrap_or_default();
+            let evicted = self.entries.remove(most_crowded);
+            self.observer.on_evict(evicted.item);
+        }
+    }
+}
+
+/// A queue's scoring function, shared between its clones.
+struct Scorer<T>(Arc<ScoreFn<T>>);
+
+type ScoreFn<T> = dyn Fn(&T) -> Vec<f64> + Send + Sync;
+
+impl<T> Clone for Scorer<T> {
+    fn clone(&self) -> Self {
+        Scorer(Arc::clone(&self.0))
+    }
+}
+
+impl<T> fmt::Debug for Scorer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Scorer")
+    }
+}
+
+fn dominates(goals: &[Goal], a: &[f64], b: &[f64]) -> bool {
+    let mut better = false;
+    for ((goal, &x), &y) in goals.iter().zip(a).zip(b) {
+        match goal.compare(x, y) {
+            Ordering::Less => return false,
+            Ordering::Greater => better = true,
+            Ordering::Equal => {}
+        }
+    }
+    better
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Goal, SkylineQueue};
+    use crate::observer::Counter;
+    use crate::util::rands;
+
+    type Point = (u32, [f64; 3]);
+    type Keys = fn(&Point) -> Vec<f64>;
+
+    fn two(p: &Point) -> Vec<f64> {
+        p.1[..2].to_vec()
+    }
+
+    fn three(p: &Point) -> Vec<f64> {
+        p.1.to_vec()
+    }
+
+    /// Random points, with coordinates from `0..range` so that small ranges
+    /// make ties.
+    fn points(n: usize, range: u8) -> Vec<Point> {
+        let mut coords = rands::<u8>().map(|c| f64::from(c % range));
+        (0..)
+            .take(n)
+            .map(|id| {
+                let mut p = [0.0; 3];
+                for c in &mut p {
+                    *c = coords.next().unwrap();
+                }
+                (id, p)
+            })
+            .collect()
+    }
+
+    /// The ids of the points no other point dominates, by comparing every pair.
+    fn oracle(q: &SkylineQueue<Point>, points: &[Point]) -> Vec<u32> {
+        points
+            .iter()
+            .filter(|p| !points.iter().any(|other| q.dominates(other, p)))
+            .map(|p| p.0)
+            .collect()
+    }
+
+    fn ids(q: &SkylineQueue<Point, impl crate::observer::Observer<Point>>) -> Vec<u32> {
+        let mut ids: Vec<u32> = q.iter().map(|p| p.0).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn skyline_matches_brute_force() {
+        let cases: [(Keys, &[Goal]); 3] = [
+            (two, &[Goal::Min, Goal::Min]),
+            (two, &[Goal::Min, Goal::Max]),
+            (three, &[Goal::Max, Goal::Min, Goal::Max]),
+        ];
+        for (keys, goals) in cases {
+            for range in [4, 50, 255] {
+                let points = points(500, range);
+                let mut q = SkylineQueue::new(goals, keys);
+                for &p in &points {
+                    q.push(p);
+                }
+                assert_eq!(oracle(&q, &points), ids(&q), "{goals:?}, range {range}");
+            }
+        }
+    }
+
+    #[test]
+    fn skyline_reports_dominated_items() {
+        let mut q =
+            SkylineQueue::new(&[Goal::Min, Goal::Min], two).with_observer(Counter::default());
+        q.push((0, [5.0, 5.0, 0.0]));
+        q.push((1, [6.0, 6.0, 0.0]));
+        q.push((2, [5.0, 5.0, 0.0]));
+        q.push((3, [1.0, 9.0, 0.0]));
+        q.push((4, [4.0, 4.0, 0.0]));
+        // 1 is rejected; 2 ties 0, so both stay until 4 evicts them.
+        assert_eq!(vec![3, 4], ids(&q));
+        let counts = *q.observer();
+        assert_eq!(
+            (4, 2, 1),
+            (counts.inserted, counts.evicted, counts.rejected)
+        );
+    }
+
+    #[test]
+    fn skyline_capacity_keeps_extremes_and_spread() {
+        // A front along the line x + y = 1000.
+        let front: Vec<Point> = (0..=100)
+            .map(|i| (i, [f64::from(i * 10), f64::from(1000 - i * 10), 0.0]))
+            .collect();
+        let mut q = SkylineQueue::new(&[Goal::Min, Goal::Min], two)
+            .with_capacity(6)
+            .with_observer(Counter::default());
+        for &p in front.iter().rev() {
+            q.push(p);
+        }
+        assert_eq!(6, q.len());
+        let ids = ids(&q);
+        assert_eq!((0, 100), (ids[0], ids[5]));
+        // Crowding keeps the survivors apart, rather than bunched at one end.
+        assert!(ids.windows(2).all(|w| w[1] - w[0] >= 10), "{ids:?}");
+        assert_eq!(95, q.observer().evicted);
+    }
+
+    #[test]
+    fn skyline_capacity_holds_a_non_dominated_subset() {
+        let points = points(2000, 255);
+        let mut q = SkylineQueue::new(&[Goal::Min, Goal::Min, Goal::Min], three).with_capacity(10);
+        for &p in &points {
+            q.push(p);
+        }
+        assert!(q.len() <= 10);
+        let kept: Vec<Point> = q.iter().copied().collect();
+        assert_eq!(ids(&q), {
+            let mut ids = oracle(&q, &kept);
+            ids.sort_unstable();
+            ids
+        });
+        let distances = q.crowding_distances();
+        assert_eq!(q.len(), distances.len());
+        assert!(distances.iter().any(|d| d.is_infinite()));
+    }
+
+    #[test]
+    fn skyline_accepts_capturing_scorers() {
+        // Scores each point by its distance from a target chosen at runtime.
+        let target = [3.0, 7.0];
+        let mut q = SkylineQueue::new(&[Goal::Min, Goal::Min], move |p: &Point| {
+            vec![(p.1[0] - target[0]).abs(), (p.1[1] - target[1]).abs()]
+        });
+        q.push((0, [0.0, 0.0, 0.0]));
+        q.push((1, [3.0, 6.0, 0.0]));
+        q.push((2, [4.0, 7.0, 0.0]));
+        q.push((3, [2.0, 9.0, 0.0]));
+        // 1 and 2 are each 1 away on one axis; 0 and 3 are beaten by both.
+        assert_eq!(vec![1, 2], ids(&q.clone()));
+    }
+
+    #[test]
+    #[should_panic(expected = "a score for each objective")]
+    fn skyline_rejects_mismatched_scores() {
+        let mut q = SkylineQueue::new(&[Goal::Min], two);
+        q.push((0, [1.0, 2.0, 3.0]));
+    }
+}
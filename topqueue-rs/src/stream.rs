@@ -0,0 +1,296 @@
+//! Top-k over async streams, with the `async` feature.
+//!
+//! [`StreamExt::top_k`] consumes a [`Stream`] into its largest items:
+//!
+//! ```
+//! # futures::executor::block_on(async {
+//! use futures::stream;
+//! use topqueue::stream::StreamExt as _;
+//!
+//! let top = stream::iter([5, 1, 9, 3, 7]).top_k(3).await;
+//! assert_eq!(vec![5, 7, 9], top);
+//! # });
+//! ```
+//!
+//! [`TopKSink`] keeps the largest items of whatever is sent into it, while a
+//! paired [`Snapshots`] stream reports them every so many items or on a timer:
+//!
+//! ```
+//! # futures::executor::block_on(async {
+//! use futures::{stream, SinkExt, StreamExt};
+//! use topqueue::stream::{Emit, TopKSink};
+//!
+//! let (sink, snapshots) = TopKSink::new(2, Emit::Items(3));
+//! stream::iter(1..=7).map(Ok).forward(sink).await.unwrap();
+//! // Snapshots after the 3rd and 6th items, and when the sink closed.
+//! let seen: Vec<Vec<i32>> = snapshots.collect().await;
+//! assert_eq!(vec![vec![2, 3], vec![5, 6], vec![6, 7]], seen);
+//! # });
+//! ```
+//!
+//! Timed snapshots need a [Tokio] runtime with its timer enabled.
+//!
+//! [Tokio]: https://tokio.rs
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures::{Sink, Stream, StreamExt as _};
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+use crate::topqueue_final::TopQueue;
+
+/// Adds [`top_k`](StreamExt::top_k) to every [`Stream`].
+pub trait StreamExt: Stream {
+    /// Consumes the stream, resolving to its `capacity` largest items,
+    /// smallest first as with [`TopQueue::into_vec`].
+    fn top_k(self, capacity: usize) -> impl Future<Output = Vec<Self::Item>>
+    where
+        Self: Sized,
+        Self::Item: Ord,
+    {
+        async move {
+            let mut stream = pin!(self);
+            let mut queue = TopQueue::new(capacity);
+            while let Some(item) = stream.next().await {
+                queue.push(item);
+            }
+            queue.into_vec()
+        }
+    }
+}
+
+impl<S: Stream + ?Sized> StreamExt for S {}
+
+/// When a [`TopKSink`] reports a snapshot, besides when it's closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    /// After every `n` items sent to the sink.
+    Items(usize),
+    /// Every period of time, while the snapshots are being polled.
+    Interval(Duration),
+}
+
+/// A [`Sink`] that retains the largest items sent into it, reporting them
+/// through its paired [`Snapshots`] stream.
+///
+/// Snapshots are buffered until read, so a consumer that falls behind sees
+/// every one of them, late.
+#[derive(Debug)]
+pub struct TopKSink<T: Ord> {
+    queue: Arc<Mutex<TopQueue<T>>>,
+    every: Option<usize>,
+    unreported: usize,
+    tx: Option<UnboundedSender<Vec<T>>>,
+}
+
+/// The snapshots reported by a [`TopKSink`], each its largest items at the
+/// time, smallest first.
+///
+/// The stream ends after the final snapshot, which is sent when the sink is
+/// closed. If the sink is dropped without being closed, it ends without one.
+#[derive(Debug)]
+pub struct Snapshots<T: Ord> {
+    queue: Arc<Mutex<TopQueue<T>>>,
+    period: Option<Duration>,
+    interval: Option<Interval>,
+    rx: UnboundedReceiver<Vec<T>>,
+}
+
+impl<T: Ord + Clone> TopKSink<T> {
+    /// Creates a sink retaining the `capacity` largest items, and the stream
+    /// of its snapshots taken as `emit` says.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `emit` is `Items(0)` or `Interval` of zero.
+    #[must_use]
+    pub fn new(capacity: usize, emit: Emit) -> (Self, Snapshots<T>) {
+        let (every, period) = match emit {
+            Emit::Items(n) => {
+                assert!(n > 0, "snapshots must be every 1 or more items");
+                (Some(n), None)
+            }
+            Emit::Interval(period) => {
+                assert!(!period.is_zero(), "snapshot interval must be non-zero");
+                (None, Some(period))
+            }
+        };
+        let queue = Arc::new(Mutex::new(TopQueue::new(capacity)));
+        let (tx, rx) = mpsc::unbounded();
+        let sink = TopKSink {
+            queue: Arc::clone(&queue),
+            every,
+            unreported: 0,
+            tx: Some(tx),
+        };
+        let snapshots = Snapshots {
+            queue,
+            period,
+            interval: None,
+            rx,
+        };
+        (sink, snapshots)
+    }
+
+    /// Returns the largest items sent so far, smallest first.
+    #[must_use]
+    pub fn snapshot(&self) -> Vec<T> {
+        snapshot(&self.queue)
+    }
+
+    fn report(&self) {
+        if let Some(tx) = &self.tx {
+            // Nobody is listening if the receiver is gone, which is fine.
+            let _ = tx.unbounded_send(self.snapshot());
+        }
+    }
+}
+
+impl<T: Ord + Clone> Sink<T> for TopKSink<T> {
+    type Error = Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Infallible> {
+        let this = self.get_mut();
+        this.queue
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(item);
+        this.unreported += 1;
+        if this.every == Some(this.unreported) {
+            this.unreported = 0;
+            this.report();
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        let this = self.get_mut();
+        this.report();
+        this.tx = None;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: Ord + Clone> Stream for Snapshots<T> {
+    type Item = Vec<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Vec<T>>> {
+        let this = self.get_mut();
+        if let Poll::Ready(next) = this.rx.poll_next_unpin(cx) {
+            return Poll::Ready(next);
+        }
+        let Some(period) = this.period else {
+            return Poll::Pending;
+        };
+        // Created on first poll, since a timer needs to be inside a runtime.
+        let interval = this.interval.get_or_insert_with(|| {
+            let mut interval = tokio::time::interval_at(Instant::now() + period, period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        });
+        match interval.poll_tick(cx) {
+            Poll::Ready(_) => Poll::Ready(Some(snapshot(&this.queue))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+fn snapshot<T: Ord + Clone>(queue: &Mutex<TopQueue<T>>) -> Vec<T> {
+    let queue = queue.lock().unwrap_or_else(PoisonError::into_inner);
+    queue
+        .page(0, queue.len())
+        .into_iter()
+        .rev()
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::channel::mpsc;
+    use futures::{stream, SinkExt, StreamExt as _};
+
+    use super::{Emit, StreamExt, TopKSink};
+    use crate::topqueue_final::TopQueue;
+    use crate::util::rands;
+
+    #[tokio::test]
+    async fn top_k_from_futures_channel() {
+        let nums: Vec<i32> = rands().take(1000).collect();
+        let (mut tx, rx) = mpsc::channel(16);
+        let producer = {
+            let nums = nums.clone();
+            async move {
+                for n in nums {
+                    tx.send(n).await.unwrap();
+                }
+            }
+        };
+        let ((), top) = tokio::join!(producer, rx.top_k(10));
+        assert_eq!(TopQueue::from_iter(10, nums).into_vec(), top);
+    }
+
+    #[tokio::test]
+    async fn top_k_from_tokio_channel_in_a_task() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let consumer = tokio::spawn(stream::poll_fn(move |cx| rx.poll_recv(cx)).top_k(3));
+        for word in ["pear", "fig", "apple", "plum", "kiwi"] {
+            tx.send(word).await.unwrap();
+        }
+        drop(tx);
+        assert_eq!(vec!["kiwi", "pear", "plum"], consumer.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn sink_snapshots_every_n_items() {
+        let (mut sink, snapshots) = TopKSink::new(2, Emit::Items(2));
+        for n in [4, 1, 8, 2, 6] {
+            sink.send(n).await.unwrap();
+        }
+        assert_eq!(vec![6, 8], sink.snapshot());
+        sink.close().await.unwrap();
+        let seen: Vec<_> = snapshots.collect().await;
+        assert_eq!(vec![vec![1, 4], vec![4, 8], vec![6, 8]], seen);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sink_snapshots_on_a_timer() {
+        let (mut sink, snapshots) = TopKSink::new(2, Emit::Interval(Duration::from_secs(1)));
+        let producer = async move {
+            sink.send(1).await.unwrap();
+            sink.send(2).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(1500)).await;
+            sink.send(3).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            sink.close().await.unwrap();
+        };
+        let ((), seen) = tokio::join!(producer, snapshots.collect::<Vec<_>>());
+        // Ticks at 1s and 2s, then the final snapshot at 2.5s.
+        assert_eq!(vec![vec![1, 2], vec![2, 3], vec![2, 3]], seen);
+    }
+
+    #[tokio::test]
+    async fn sink_without_listener() {
+        let (mut sink, snapshots) = TopKSink::new(1, Emit::Items(1));
+        drop(snapshots);
+        sink.send('a').await.unwrap();
+        sink.close().await.unwrap();
+        assert_eq!(vec!['a'], sink.snapshot());
+    }
+}
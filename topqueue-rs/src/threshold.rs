@@ -0,0 +1,377 @@
+//! Top-k over several ranked lists, reading only as much of them as needed.
+//!
+//! When each item is scored in several lists, say on relevance, freshness
+//! and popularity, each sorted from the highest score down, the top items by
+//! an aggregate of their scores can often be found without reading every
+//! list to the end. Fagin's Threshold Algorithm[^ta] reads the lists in
+//! parallel, looking up each new item's scores in the other lists, and stops
+//! once the items it has are provably better than any it hasn't seen.
+//! [`threshold_algorithm`] implements it, and [`no_random_access`] the
+//! variant for lists that can only be read in order, which keeps bounds on
+//! the scores of partly seen items instead.
+//!
+//! The aggregate must be *monotone*: raising any of an item's scores mustn't
+//! lower its aggregate, as with sums, weighted sums, minimums and maximums.
+//! Scores are assumed to be non-negative, and an item missing from a list
+//! scores 0 there.
+//!
+//! ```
+//! # use topqueue::threshold::threshold_algorithm;
+//! let relevance = vec![("a", 0.9), ("b", 0.8), ("c", 0.3), ("d", 0.2)];
+//! let freshness = vec![("b", 0.9), ("a", 0.7), ("d", 0.5), ("c", 0.1)];
+//! let lookup = [&relevance, &freshness];
+//! let ranking = threshold_algorithm(
+//!     vec![relevance.clone().into_iter(), freshness.clone().into_iter()],
+//!     1,
+//!     |scores| scores.iter().sum(),
+//!     |list, key| lookup[list].iter().find(|e| e.0 == *key).map(|e| e.1),
+//! );
+//! assert_eq!("b", ranking.top[0].0);
+//! // Two rounds of reading both lists were enough, out of four.
+//! assert_eq!(4, ranking.sorted_accesses);
+//! assert_eq!(2, ranking.random_accesses);
+//! assert_eq!(2, ranking.saved());
+//! ```
+//!
+//! [^ta]: Fagin, Lotem and Naor, "Optimal Aggregation Algorithms for
+//!     Middleware", Journal of Computer and System Sciences, 2003.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::topqueue_final::TopQueue;
+use crate::util::OrdF64;
+
+/// The top items found, and the work it took to find them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ranking<K> {
+    /// The top items and their aggregate scores, largest first.
+    ///
+    /// From [`no_random_access`], each score is a lower bound, exact for the
+    /// items that were seen in every list.
+    pub top: Vec<(K, f64)>,
+    /// Entries read from the lists in order.
+    pub sorted_accesses: usize,
+    /// Scores looked up by key.
+    pub random_accesses: usize,
+    /// The entries in all the lists, which a full scan would have read.
+    pub total_entries: usize,
+}
+
+impl<K> Ranking<K> {
+    /// Returns how many fewer accesses were made than reading every entry of
+    /// every list.
+    #[must_use]
+    pub fn saved(&self) -> usize {
+        self.total_entries
+            .saturating_sub(self.sorted_accesses + self.random_accesses)
+    }
+}
+
+/// Reads the lists in order, one entry from each per round, until they all
+/// run out.
+struct Rounds<I> {
+    lists: Vec<I>,
+    /// The last score read from each list, or 0 once it has run out.
+    last: Vec<f64>,
+    total_entries: usize,
+    sorted_accesses: usize,
+}
+
+impl<K, I: ExactSizeIterator<Item = (K, f64)>> Rounds<I> {
+    fn new(lists: Vec<I>) -> Self {
+        Rounds {
+            last: vec![f64::INFINITY; lists.len()],
+            total_entries: lists.iter().map(ExactSizeIterator::len).sum(),
+            lists,
+            sorted_accesses: 0,
+        }
+    }
+
+    /// Reads the next round, returning each list's entry, if any, or `None`
+    /// once every list has run out.
+    fn next_round(&mut self) -> Option<Vec<Option<(K, f64)>>> {
+        let round: Vec<_> = self.lists.iter_mut().map(Iterator::next).collect();
+        if round.iter().all(Option::is_none) {
+            return None;
+        }
+        for (last, entry) in self.last.iter_mut().zip(&round) {
+            *last = entry.as_ref().map_or(0.0, |e| e.1);
+        }
+        self.sorted_accesses += round.iter().flatten().count();
+        Some(round)
+    }
+
+    fn ranking(&self, top: Vec<(K, f64)>, random_accesses: usize) -> Ranking<K> {
+        Ranking {
+            top,
+            sorted_accesses: self.sorted_accesses,
+            random_accesses,
+            total_entries: self.total_entries,
+        }
+    }
+}
+
+/// Ranks by score, ties going to the lowest key.
+type Candidates<K> = TopQueue<(OrdF64, Reverse<K>)>;
+
+fn into_top<K: Ord>(candidates: Candidates<K>) -> Vec<(K, f64)> {
+    let mut top: Vec<_> = candidates
+        .into_iter()
+        .map(|(score, key)| (key.0, score.0))
+        .collect();
+    top.reverse();
+    top
+}
+
+/// Returns the `k` items with the largest aggregate scores across `lists`,
+/// by Fagin's Threshold Algorithm.
+///
+/// Each list yields `(key, score)` entries from the highest score down, and
+/// `random_access(i, key)` looks up `key`'s score in list `i`. The lists are
+/// read a round at a time, each item's missing scores are looked up as it's
+/// first seen, and reading stops once the `k`th best aggregate is at least
+/// the aggregate of the last scores read, which bounds every unseen item.
+/// Among items tied with the `k`th, which are returned is unspecified.
+pub fn threshold_algorithm<K, I>(
+    lists: Vec<I>,
+    k: usize,
+    aggregate: impl Fn(&[f64]) -> f64,
+    mut random_access: impl FnMut(usize, &K) -> Option<f64>,
+) -> Ranking<K>
+where
+    K: Ord + Clone,
+    I: ExactSizeIterator<Item = (K, f64)>,
+{
+    let mut rounds = Rounds::new(lists);
+    let mut candidates: Candidates<K> = TopQueue::new(k);
+    let mut seen = BTreeSet::new();
+    let mut random_accesses = 0;
+    let mut scores = vec![0.0; rounds.lists.len()];
+    while let Some(round) = rounds.next_round() {
+        for (i, entry) in round.into_iter().enumerate() {
+            let Some((key, score)) = entry else {
+                continue;
+            };
+            if !seen.insert(key.clone()) {
+                continue;
+            }
+            for (j, s) in scores.iter_mut().enumerate() {
+                *s = if j == i {
+                    score
+                } else {
+                    random_accesses += 1;
+                    random_access(j, &key).unwrap_or(0.0)
+                };
+            }
+            candidates.push((OrdF64(aggregate(&scores)), Reverse(key)));
+        }
+
+        let threshold = aggregate(&rounds.last);
+        if candidates.len() == k
+            && candidates
+                .peek_min()
+                .is_none_or(|min| min.0 .0 >= threshold)
+        {
+            break;
+        }
+    }
+    rounds.ranking(into_top(candidates), random_accesses)
+}
+
+/// Returns the `k` items with the largest aggregate scores across `lists`,
+/// by the No-Random-Access algorithm.
+///
+/// Each list yields `(key, score)` entries from the highest score down, and
+/// is only ever read in order. Each item seen gets a lower bound, its
+/// aggregate with unseen scores taken as 0, and an upper bound, with each
+/// unseen score taken as the last read from its list. Reading stops once `k`
+/// items' lower bounds are at least every other item's upper bound, seen or
+/// not, and their lower bounds are returned as their scores.
+pub fn no_random_access<K, I>(
+    lists: Vec<I>,
+    k: usize,
+    aggregate: impl Fn(&[f64]) -> f64,
+) -> Ranking<K>
+where
+    K: Ord + Clone,
+    I: ExactSizeIterator<Item = (K, f64)>,
+{
+    let mut rounds = Rounds::new(lists);
+    let mut seen: BTreeMap<K, Vec<Option<f64>>> = BTreeMap::new();
+    let m = rounds.lists.len();
+    let bound = |known: &[Option<f64>], unknown: &dyn Fn(usize) -> f64| {
+        let scores: Vec<f64> = (0..m)
+            .map(|i| known[i].unwrap_or_else(|| unknown(i)))
+            .collect();
+        aggregate(&scores)
+    };
+    let lower_bounds = |seen: &BTreeMap<K, Vec<Option<f64>>>| {
+        TopQueue::from_iter(
+            k,
+            seen.iter()
+                .map(|(key, known)| (OrdF64(bound(known, &|_| 0.0)), Reverse(key.clone()))),
+        )
+    };
+
+    while let Some(round) = rounds.next_round() {
+        for (i, entry) in round.into_iter().enumerate() {
+            if let Some((key, score)) = entry {
+                seen.entry(key).or_insert_with(|| vec![None; m])[i] = Some(score);
+            }
+        }
+
+        let top = lower_bounds(&seen);
+        if top.len() < k {
+            continue;
+        }
+        let Some(&(OrdF64(kth), _)) = top.peek_min() else {
+            break;
+        };
+        let last = &rounds.last;
+        let top_keys: BTreeSet<&K> = top.iter().map(|(_, key)| &key.0).collect();
+        let proven = aggregate(last) <= kth
+            && seen
+                .iter()
+                .all(|(key, known)| top_keys.contains(key) || bound(known, &|i| last[i]) <= kth);
+        if proven {
+            break;
+        }
+    }
+    let top = into_top(lower_bounds(&seen));
+    rounds.ranking(top, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{no_random_access, threshold_algorithm};
+    use crate::util::rands;
+
+    type List = Vec<(u32, f64)>;
+
+    /// `m` lists over items `0..n`, each missing a few items. With
+    /// `correlated`, an item's scores are close across lists, as when the
+    /// lists agree on what's good.
+    fn lists(n: u32, m: usize, correlated: bool) -> Vec<List> {
+        let mut noise = rands::<f64>();
+        let quality: Vec<f64> = noise.by_ref().take(n as usize).collect();
+        (0..m)
+            .map(|_| {
+                let mut list: List = (0..n)
+                    .filter_map(|id| {
+                        let (keep, x) = (noise.next()?, noise.next()?);
+                        let score = if correlated {
+                            quality[id as usize] * 0.9 + x * 0.1
+                        } else {
+                            x
+                        };
+                        (keep > 0.05).then_some((id, score))
+                    })
+                    .collect();
+                list.sort_by(|a, b| b.1.total_cmp(&a.1));
+                list
+            })
+            .collect()
+    }
+
+    /// Aggregates every item's scores across every list, and sorts them all.
+    fn oracle(lists: &[List], k: usize, aggregate: fn(&[f64]) -> f64) -> Vec<(u32, f64)> {
+        let mut scores: HashMap<u32, Vec<f64>> = HashMap::new();
+        for (i, list) in lists.iter().enumerate() {
+            for &(id, score) in list {
+                scores.entry(id).or_insert_with(|| vec![0.0; lists.len()])[i] = score;
+            }
+        }
+        let mut all: Vec<(u32, f64)> = scores
+            .into_iter()
+            .map(|(id, s)| (id, aggregate(&s)))
+            .collect();
+        all.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        all.truncate(k);
+        all
+    }
+
+    fn sum(scores: &[f64]) -> f64 {
+        scores.iter().sum()
+    }
+
+    fn min(scores: &[f64]) -> f64 {
+        scores.iter().copied().fold(f64::INFINITY, f64::min)
+    }
+
+    fn weighted(scores: &[f64]) -> f64 {
+        scores.iter().zip([3.0, 2.0, 1.0]).map(|(s, w)| s * w).sum()
+    }
+
+    fn iters(lists: &[List]) -> Vec<std::vec::IntoIter<(u32, f64)>> {
+        lists.iter().map(|l| l.clone().into_iter()).collect()
+    }
+
+    #[test]
+    fn threshold_algorithm_matches_full_scan() {
+        for correlated in [false, true] {
+            let lists = lists(500, 3, correlated);
+            let index: Vec<HashMap<u32, f64>> =
+                lists.iter().map(|l| l.iter().copied().collect()).collect();
+            for aggregate in [sum, min, weighted] {
+                for k in [0, 1, 10, 600] {
+                    let ranking = threshold_algorithm(iters(&lists), k, aggregate, |i, id| {
+                        index[i].get(id).copied()
+                    });
+                    assert_eq!(oracle(&lists, k, aggregate), ranking.top);
+                    assert!(ranking.sorted_accesses <= ranking.total_entries);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn no_random_access_matches_full_scan() {
+        for correlated in [false, true] {
+            let lists = lists(300, 3, correlated);
+            for aggregate in [sum, min, weighted] {
+                for k in [0, 1, 10, 400] {
+                    let ranking = no_random_access(iters(&lists), k, aggregate);
+                    let expected = oracle(&lists, k, aggregate);
+                    let ids = |top: &[(u32, f64)]| {
+                        let mut ids: Vec<u32> = top.iter().map(|e| e.0).collect();
+                        ids.sort_unstable();
+                        ids
+                    };
+                    assert_eq!(ids(&expected), ids(&ranking.top));
+                    assert_eq!(0, ranking.random_accesses);
+                    // Each lower bound is at most the item's true score.
+                    let scores: HashMap<u32, f64> = expected.iter().copied().collect();
+                    for (id, bound) in &ranking.top {
+                        assert!(*bound <= scores[id], "{id}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn agreeing_lists_stop_early() {
+        let lists = lists(2000, 3, true);
+        let index: Vec<HashMap<u32, f64>> =
+            lists.iter().map(|l| l.iter().copied().collect()).collect();
+        let ta = threshold_algorithm(iters(&lists), 10, sum, |i, id| index[i].get(id).copied());
+        let nra = no_random_access(iters(&lists), 10, sum);
+        // Most of each list is never read.
+        assert!(
+            ta.saved() > ta.total_entries / 2,
+            "{} of {}",
+            ta.saved(),
+            ta.total_entries
+        );
+        assert!(
+            nra.saved() > nra.total_entries / 2,
+            "{} of {}",
+            nra.saved(),
+            nra.total_entries
+        );
+        assert_eq!(oracle(&lists, 10, sum), ta.top);
+    }
+}
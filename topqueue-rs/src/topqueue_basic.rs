@@ -19,23 +19,60 @@
 use std::cmp::Reverse;
 use std::collections::binary_heap::BinaryHeap;
 
+#[cfg(feature = "instrument")]
+use crate::instrument::{Counted, Stats};
+
+/// The heap holds plain `i32`s, or with the `instrument` feature, `i32`s
+/// whose comparisons are counted.
+#[cfg(not(feature = "instrument"))]
+type Item = i32;
+#[cfg(feature = "instrument")]
+type Item = Counted<i32>;
+
+#[cfg(not(feature = "instrument"))]
+fn wrap(n: i32) -> Item {
+    n
+}
+
+#[cfg(feature = "instrument")]
+fn wrap(n: i32) -> Item {
+    Counted(n)
+}
+
+#[cfg(not(feature = "instrument"))]
+fn unwrap(item: Item) -> i32 {
+    item
+}
+
+#[cfg(feature = "instrument")]
+fn unwrap(item: Item) -> i32 {
+    item.0
+}
+
 /// A collection that retains the largest n items inserted into it.
 ///
 /// Implemented using `std::collections::binary_heap<Reverse<i32>>`.
 #[derive(Debug)]
 pub struct TopQueue {
     capacity: usize,
-    queue: BinaryHeap<Reverse<i32>>,
+    queue: BinaryHeap<Reverse<Item>>,
+    #[cfg(feature = "instrument")]
+    stats: Stats,
 }
 
 impl TopQueue {
     /// Create a new `TopQueue` that tracks the largest
     /// `capacity` number of inserted items.
+    ///
+    /// The heap is allocated as items arrive, and never grows past
+    /// `capacity` items. A queue of capacity 0 ignores every value.
     #[must_use]
     pub fn new(capacity: usize) -> Self {
         TopQueue {
             capacity,
-            queue: BinaryHeap::with_capacity(capacity),
+            queue: BinaryHeap::new(),
+            #[cfg(feature = "instrument")]
+            stats: Stats::default(),
         }
     }
 
@@ -45,6 +82,27 @@ impl TopQueue {
         self.capacity
     }
 
+    /// Changes the maximum number of values the queue will retain.
+    ///
+    /// Growing the queue only raises the limit. Shrinking it evicts the
+    /// smallest values until the queue fits, and returns them in ascending
+    /// order.
+    pub fn set_capacity(&mut self, capacity: usize) -> Vec<i32> {
+        let excess = self.queue.len().saturating_sub(capacity);
+        let evicted = (0..excess)
+            .filter_map(|_| self.queue.pop())
+            .map(|r| unwrap(r.0))
+            .collect();
+        self.capacity = capacity;
+        evicted
+    }
+
+    /// Releases any memory held by the underlying heap beyond what its
+    /// current contents need.
+    pub fn shrink_to_fit(&mut self) {
+        self.queue.shrink_to_fit();
+    }
+
     /// Returns a Vec of the values contained in the queue in the
     /// order they would be returned by the underlying `binary_heap`.
     ///
@@ -54,7 +112,7 @@ impl TopQueue {
         // BinaryHeap doesn't allow draining in sorted order,
         // and the into_iter_sorted() method is unstable.
         // So pop() values one at a time.
-        self.queue.drain().map(|r| r.0).collect()
+        self.queue.drain().map(|r| unwrap(r.0)).collect()
     }
 
     /// Returns true if the underlying queue length is 0.
@@ -71,14 +129,37 @@ impl TopQueue {
         self.queue.len()
     }
 
+    /// Returns the underlying heap's items in array order, for
+    /// [`render`](crate::render).
+    pub(crate) fn heap_items(&self) -> impl Iterator<Item = i32> + '_ {
+        self.queue.as_slice().iter().map(|r| unwrap(r.0))
+    }
+
+    /// Returns counts of what pushes into the queue have done.
+    #[cfg(feature = "instrument")]
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
     /// Attempts to insert the value `n` into the queue.
     ///
     /// If the value is less than the smallest already in the queue, it is ignored.
     pub fn push(&mut self, n: i32) {
-        let rev_n = Reverse(n);
+        let rev_n = Reverse(wrap(n));
+        #[cfg(feature = "instrument")]
+        {
+            self.stats.pushes += 1;
+        }
 
-        // If we're under capacity, just push
+        // If we're under capacity, just push, doubling the heap's
+        // allocation when it's full but not past the capacity
         if self.queue.len() < self.capacity {
+            let len = self.queue.len();
+            if len == self.queue.capacity() {
+                let target = len.saturating_mul(2).max(4).min(self.capacity);
+                self.queue.reserve_exact(target - len);
+            }
             self.queue.push(rev_n);
         // If new value is greater than the smallest in the queue, push
         // (The underlying BinaryHeap<Reverse<_>> means the comparison
@@ -86,6 +167,15 @@ impl TopQueue {
         } else if Some(&rev_n) < self.queue.peek() {
             self.queue.pop();
             self.queue.push(rev_n);
+            #[cfg(feature = "instrument")]
+            {
+                self.stats.replacements += 1;
+            }
+        } else {
+            #[cfg(feature = "instrument")]
+            {
+                self.stats.rejections += 1;
+            }
         }
     }
 }
@@ -139,4 +229,46 @@ mod tests {
             (i32::MAX - min_top) < top_000001
         });
     }
+
+    #[test]
+    fn topq_allocates_lazily_up_to_capacity() {
+        let mut q = TopQueue::new(usize::MAX);
+        assert_eq!(0, q.queue.capacity());
+        q.push(1);
+        // The allocator may hand back more than was asked for, so only bound it.
+        assert!((1..2000).contains(&q.queue.capacity()));
+
+        let mut q = TopQueue::new(6);
+        for n in 0..100 {
+            q.push(n);
+        }
+        assert!(q.queue.capacity() <= 6);
+
+        let mut q = TopQueue::new(0);
+        q.push(1);
+        assert!(q.is_empty());
+        assert_eq!(0, q.queue.capacity());
+    }
+
+    #[test]
+    fn topq_set_capacity_shrinks_and_grows() {
+        let mut q = TopQueue::new(5);
+        for n in [9, 2, 7, 4, 5] {
+            q.push(n);
+        }
+        q.shrink_to_fit();
+
+        assert_eq!(vec![2, 4], q.set_capacity(3));
+        assert_eq!(3, q.len());
+        assert_eq!(3, q.capacity());
+
+        assert!(q.set_capacity(4).is_empty());
+        q.push(1);
+        q.push(8);
+        assert_eq!(4, q.len());
+
+        let mut output = q.into_vec();
+        output.sort_unstable();
+        assert_eq!(output, vec![5, 7, 8, 9]);
+    }
 }
@@ -18,58 +18,270 @@
 
 use std::cmp::Reverse;
 use std::collections::binary_heap::BinaryHeap;
-use std::iter::from_fn;
+use std::collections::TryReserveError;
+use std::error::Error;
+use std::fmt;
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::Arc;
+
+use crate::heap::{Heap, IntoIterSorted, MinMaxHeap};
+use crate::observer::{NoopObserver, Observer};
+use crate::util::OrdF64;
 
 /// A collection that retains the largest n items inserted into it.
 ///
 /// Implemented using `std::collections::binary_heap<Reverse<_>>`.
-#[derive(Debug)]
-pub struct TopQueue<T: Ord> {
+///
+/// A queue created with [`TopQueue::with_weigher`] is additionally bounded by
+/// the total weight of its items, as measured by a user-supplied function.
+///
+/// The [`Observer`] `O` is notified as items are inserted, evicted or
+/// rejected. The default, [`NoopObserver`], does nothing and costs nothing.
+///
+/// The items are held in the min-heap `H`, which can be swapped for one of the
+/// alternatives in [`heap`](crate::heap).
+///
+/// Cloning a queue clones its items and its observer. Two queues are equal
+/// if they have the same capacity and weight limits and hold the same items,
+/// however those are laid out in their heaps. Iterating over a queue, by
+/// value or by reference, yields its items smallest first, and so does
+/// displaying it. To [`collect`](Iterator::collect) into a queue, use
+/// [`TopK`].
+#[derive(Debug, Clone)]
+pub struct TopQueue<T: Ord, O: Observer<T> = NoopObserver, H: Heap<T> = BinaryHeap<Reverse<T>>> {
     capacity: usize,
-    queue: BinaryHeap<Reverse<T>>,
+    queue: H,
+    weigher: Option<Weigher<T>>,
+    budget: usize,
+    max_item_weight: usize,
+    weight: usize,
+    observer: O,
 }
 
 impl<T: Ord> TopQueue<T> {
     /// Create a new `TopQueue` that tracks the largest `capacity` number of inserted items.
+    ///
+    /// Nothing is allocated until items arrive, and the heap then grows as
+    /// they do, never past `capacity` items. So a generous capacity costs
+    /// nothing up front. A queue of capacity 0 retains nothing, and rejects
+    /// every item pushed into it.
     #[must_use]
     pub fn new(capacity: usize) -> Self {
         TopQueue {
             capacity,
-            queue: BinaryHeap::with_capacity(capacity),
+            queue: Heap::with_capacity(0),
+            weigher: None,
+            budget: usize::MAX,
+            max_item_weight: usize::MAX,
+            weight: 0,
+            observer: NoopObserver,
         }
     }
 
-    /// Returns the maximum number of values the queue will retain.
+    /// Create a new `TopQueue` like [`TopQueue::new`], but first checking
+    /// that `capacity` items could ever fit in memory.
+    ///
+    /// Nothing is allocated up front, so a capacity read from configuration
+    /// costs nothing until items arrive. Allocating as they do can still
+    /// fail, which [`TopQueue::try_push`] reports.
+    ///
+    /// ```
+    /// # use topqueue::topqueue_final::TopQueue;
+    /// assert!(TopQueue::<u64>::try_new(1 << 40).is_ok());
+    /// assert!(TopQueue::<u64>::try_new(usize::MAX).is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if `capacity` items would take more bytes than `isize::MAX`, the
+    /// most any allocation can hold.
+    pub fn try_new(capacity: usize) -> Result<Self, CapacityError> {
+        let fits = capacity
+            .checked_mul(mem::size_of::<T>())
+            .is_some_and(|bytes| bytes <= isize::MAX.unsigned_abs());
+        if !fits {
+            return Err(CapacityError { capacity });
+        }
+        Ok(TopQueue::new(capacity))
+    }
+
+    /// Create a new `TopQueue` that retains the largest inserted items whose
+    /// combined weight, as reported by `weigher`, does not exceed `budget`.
+    ///
+    /// The queue never holds more than `capacity` items either; pass
+    /// `usize::MAX` to bound it by weight alone. The weigher may capture
+    /// state, such as a table of sizes, and is shared by clones of the queue.
+    ///
+    /// ```
+    /// # use topqueue::topqueue_final::TopQueue;
+    /// let mut q = TopQueue::with_weigher(usize::MAX, 10, |s: &String| s.len());
+    /// for word in ["pear", "fig", "banana", "apple"] {
+    ///     q.push(word.to_string());
+    /// }
+    /// assert_eq!(7, q.total_weight());
+    /// assert_eq!(vec!["fig", "pear"], q.into_vec());
+    /// ```
     #[must_use]
-    pub fn capacity(&self) -> usize {
-        self.capacity
+    pub fn with_weigher<W>(capacity: usize, budget: usize, weigher: W) -> Self
+    where
+        W: Fn(&T) -> usize + Send + Sync + 'static,
+    {
+        TopQueue {
+            capacity,
+            queue: Heap::with_capacity(0),
+            weigher: Some(Weigher(Arc::new(weigher))),
+            budget,
+            max_item_weight: budget,
+            weight: 0,
+            observer: NoopObserver,
+        }
     }
 
     /// Creates a new `TopQueue` of capacity `capacity` and with the elements of
     /// `iter` pushed into it.
+    ///
+    /// This isn't [`FromIterator::from_iter`], which can't be given a
+    /// capacity. Collect into a [`TopK`] for that.
     pub fn from_iter<I: IntoIterator<Item = T>>(capacity: usize, iter: I) -> Self {
         iter.into_iter().fold(TopQueue::new(capacity), |mut q, x| {
             q.push(x);
             q
         })
     }
+}
+
+impl<T: Ord, O: Observer<T>, H: Heap<T>> TopQueue<T, O, H> {
+    /// Replaces the queue's observer, keeping its contents and settings.
+    ///
+    /// See [`Observer`] for the events that are reported.
+    #[must_use]
+    pub fn with_observer<O2: Observer<T>>(self, observer: O2) -> TopQueue<T, O2, H> {
+        TopQueue {
+            capacity: self.capacity,
+            queue: self.queue,
+            weigher: self.weigher,
+            budget: self.budget,
+            max_item_weight: self.max_item_weight,
+            weight: self.weight,
+            observer,
+        }
+    }
+
+    /// Moves the queue's contents into a different kind of heap, keeping its
+    /// observer and settings.
+    ///
+    /// See [`heap`](crate::heap) for the alternatives.
+    #[must_use]
+    pub fn with_heap<H2: Heap<T>>(self) -> TopQueue<T, O, H2> {
+        TopQueue {
+            capacity: self.capacity,
+            queue: H2::from_vec(self.queue.into_vec()),
+            weigher: self.weigher,
+            budget: self.budget,
+            max_item_weight: self.max_item_weight,
+            weight: self.weight,
+            observer: self.observer,
+        }
+    }
+
+    /// Returns a reference to the queue's observer.
+    #[must_use]
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Returns a mutable reference to the queue's observer.
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    /// Consumes the queue, returning its observer.
+    #[must_use]
+    pub fn into_observer(self) -> O {
+        self.observer
+    }
+
+    /// Sets the heaviest weight a single item may have and still be admitted.
+    ///
+    /// Defaults to the full budget. Has no effect on an unweighted queue.
+    #[must_use]
+    pub fn with_max_item_weight(mut self, max_item_weight: usize) -> Self {
+        self.max_item_weight = max_item_weight.min(self.budget);
+        self
+    }
+
+    /// Returns the weight budget, or `None` if the queue is unweighted.
+    #[must_use]
+    pub fn weight_budget(&self) -> Option<usize> {
+        self.weigher.as_ref().map(|_| self.budget)
+    }
+
+    /// Returns the heaviest weight a single item may have, or `None` if the
+    /// queue is unweighted.
+    #[must_use]
+    pub fn max_item_weight(&self) -> Option<usize> {
+        self.weigher.as_ref().map(|_| self.max_item_weight)
+    }
+
+    /// Returns the combined weight of the items in the queue.
+    ///
+    /// Always 0 for a queue created without a weigher.
+    #[must_use]
+    pub fn total_weight(&self) -> usize {
+        self.weight
+    }
+
+    fn weigh(&self, item: &T) -> usize {
+        self.weigher.as_ref().map_or(0, |weigher| (weigher.0)(item))
+    }
+
+    /// Pops the smallest item, keeping the total weight up to date.
+    fn pop_min(&mut self) -> Option<T> {
+        let item = self.queue.pop_min()?;
+        self.weight -= self.weigh(&item);
+        Some(item)
+    }
+
+    /// Returns the maximum number of values the queue will retain.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Changes the maximum number of values the queue will retain.
+    ///
+    /// Growing the queue only raises the limit. Shrinking it evicts the
+    /// smallest values until the queue fits, and returns them in ascending
+    /// order.
+    pub fn set_capacity(&mut self, capacity: usize) -> Vec<T> {
+        self.observer.on_set_capacity(capacity);
+        let excess = self.queue.len().saturating_sub(capacity);
+        let evicted = (0..excess).filter_map(|_| self.pop_min()).collect();
+        self.capacity = capacity;
+        evicted
+    }
+
+    /// Releases any memory held by the underlying heap beyond what its
+    /// current contents need.
+    pub fn shrink_to_fit(&mut self) {
+        self.queue.shrink_to_fit();
+    }
 
     /// Returns a Vec of the values contained in the queue in the
-    /// order they would be returned by the underlying `binary_heap`.
+    /// order they would be popped from the underlying heap, smallest first.
     ///
     /// Consumes the contents of the queue.
     #[must_use]
-    pub fn into_vec(mut self) -> Vec<T> {
-        // `BinaryHeap` doesn't allow draining in sorted order,
-        // and the `into_iter_sorted()` method is unstable.
-        // This creates a `Vec<T>` populated by `pop()`ping values one at a time.
-        from_fn(|| self.queue.pop().map(|r| r.0)).collect()
+    pub fn into_vec(self) -> Vec<T> {
+        self.queue.into_sorted_vec()
     }
 
     /// Returns true if the underlying queue length is 0.
     #[must_use]
     pub fn is_empty(&self) -> bool {
-        self.queue.len() == 0
+        self.queue.is_empty()
     }
 
     /// Returns the number of elements currently in the `TopQueue`.
@@ -80,29 +292,590 @@ impl<T: Ord> TopQueue<T> {
         self.queue.len()
     }
 
+    /// Returns the smallest item in the queue, the next to be evicted, in
+    /// *O*(1).
+    #[must_use]
+    pub fn peek_min(&self) -> Option<&T> {
+        self.queue.peek_min()
+    }
+
+    /// Returns an iterator over the queue's items, smallest first.
+    ///
+    /// The items are sorted when the iterator is created, in
+    /// *O*(*n* log *n*).
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut items: Vec<&T> = self.queue.items().collect();
+        items.sort_unstable();
+        Iter {
+            items: items.into_iter(),
+        }
+    }
+
+    /// Returns the underlying heap, for [`render`](crate::render).
+    pub(crate) fn heap(&self) -> &H {
+        &self.queue
+    }
+
+    /// Returns the items ranked `offset` to `offset + limit - 1`, largest
+    /// first, where the largest item in the queue has rank 0.
+    ///
+    /// The page is cut short if it runs past the items in the queue, and is
+    /// empty if it starts past them. The queue only knows its top
+    /// `capacity` items, so no page extends beyond that rank. Equal items
+    /// hold consecutive ranks, in unspecified order among themselves.
+    ///
+    /// This is *O*(*n* + *m* log *m*) for a queue of *n* items and a page ending
+    /// at rank *m*. To page over a stream without keeping a queue, use
+    /// [`top_page`](crate::util::top_page).
+    ///
+    /// ```
+    /// # use topqueue::topqueue_final::TopQueue;
+    /// let q = TopQueue::from_iter(10, 1..=100);
+    /// assert_eq!(vec![&97, &96, &95], q.page(3, 3));
+    /// assert_eq!(vec![&92, &91], q.page(8, 5));
+    /// assert!(q.page(10, 5).is_empty());
+    /// ```
+    #[must_use]
+    pub fn page(&self, offset: usize, limit: usize) -> Vec<&T> {
+        let mut items: Vec<&T> = self.queue.items().collect();
+        let end = offset.saturating_add(limit).min(items.len());
+        if offset >= end {
+            return Vec::new();
+        }
+        // Gather the top `end` items at the front before sorting only those.
+        items.select_nth_unstable_by(end - 1, |a, b| b.cmp(a));
+        items.truncate(end);
+        items.sort_unstable_by(|a, b| b.cmp(a));
+        items.split_off(offset)
+    }
+
+    /// Removes one item equal to `item` from the queue and returns it, or
+    /// `None` if no such item is present.
+    ///
+    /// This is a linear search followed by a heap rebuild, so it is *O*(*n*).
+    pub fn remove(&mut self, item: &T) -> Option<T> {
+        let mut items = mem::replace(&mut self.queue, H::with_capacity(0)).into_vec();
+        let removed = items
+            .iter()
+            .position(|x| x == item)
+            .map(|i| items.swap_remove(i));
+        self.queue = H::from_vec(items);
+        if let Some(removed) = &removed {
+            self.weight -= self.weigh(removed);
+        }
+        removed
+    }
+
+    /// Retains only the items for which `f` returns `true`, discarding the rest.
+    ///
+    /// Each item is visited exactly once, in unspecified order.
+    /// This is *O*(*n*) and rebuilds the heap at most once.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let (weigher, weight) = (&self.weigher, &mut self.weight);
+        self.queue.retain(|x| {
+            let keep = f(x);
+            if let (false, Some(weigher)) = (keep, weigher) {
+                *weight -= (weigher.0)(x);
+            }
+            keep
+        });
+    }
+
+    /// Removes every item from the queue, returning them in ascending order.
+    ///
+    /// Each item yielded costs *O*(log *n*). The queue is left empty when the
+    /// iterator is dropped, even if it was not fully consumed.
+    pub fn drain_sorted(&mut self) -> DrainSorted<'_, T, H> {
+        self.weight = 0;
+        DrainSorted {
+            queue: &mut self.queue,
+            item: PhantomData,
+        }
+    }
+
+    /// Removes every item from the queue. The capacity is unchanged.
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.weight = 0;
+    }
+
     /// Attempts to insert the value `item` into the queue.
     ///
     /// If the value is less than the smallest already in the queue, it is ignored.
+    ///
+    /// In a weighted queue, an item heavier than the per-item maximum is
+    /// ignored. Otherwise it is inserted and the smallest items are evicted
+    /// until both the capacity and the weight budget are respected, which may
+    /// evict the new item itself. In that case the observer sees the item
+    /// inserted and then evicted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the heap needs to grow and the memory can't be allocated.
+    /// Use [`try_push`](TopQueue::try_push) to handle that instead.
     pub fn push(&mut self, item: T) {
-        let rev_item = Reverse(item);
+        if let Err(err) = self.try_push(item) {
+            panic!("{err}");
+        }
+    }
+
+    /// Inserts `item` as [`push`](TopQueue::push) does, unless the heap needs
+    /// to grow and can't.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the memory for the item can't be allocated, handing the item
+    /// back in the error. The queue and its observer are left unchanged.
+    pub fn try_push(&mut self, item: T) -> Result<(), AllocError<T>> {
+        if self.weigher.is_some() {
+            return self.push_weighted(item);
+        }
 
         // If we're under capacity, just push
         if self.queue.len() < self.capacity {
-            self.queue.push(rev_item);
-        // If new value is greater than the smallest in the queue, push
-        // (The underlying BinaryHeap<Reverse<_>> means the comparison
-        // operators are reversed.)
-        } else if Some(&rev_item) <= self.queue.peek() {
-            self.queue.pop();
-            self.queue.push(rev_item);
+            if let Err(source) = self.try_grow() {
+                return Err(AllocError { item, source });
+            }
+            self.observer.on_insert(&item);
+            self.queue.push(item);
+        // If new value is at least the smallest in the queue, it takes the
+        // smallest's place, with a single sift through the heap.
+        } else if self.queue.peek_min().is_some_and(|min| &item >= min) {
+            let observer = &mut self.observer;
+            self.queue.replace_min_with(item, |evicted, item| {
+                if let Some(evicted) = evicted {
+                    observer.on_evict(evicted);
+                }
+                observer.on_insert(item);
+            });
+        } else {
+            self.observer.on_reject(item);
+        }
+        Ok(())
+    }
+
+    fn push_weighted(&mut self, item: T) -> Result<(), AllocError<T>> {
+        let weight = self.weigh(&item);
+        // A full queue can only admit items at least as large as its smallest.
+        if weight > self.max_item_weight
+            || self.queue.len() >= self.capacity
+                && self.queue.peek_min().is_none_or(|min| &item < min)
+        {
+            self.observer.on_reject(item);
+            return Ok(());
+        }
+
+        // Weights near `usize::MAX` can overflow the total, which would then
+        // exceed any budget, so smaller items are evicted until it fits.
+        let total = loop {
+            if let Some(total) = self.weight.checked_add(weight) {
+                break total;
+            }
+            if self.queue.peek_min().is_some_and(|min| min < &item) {
+                if let Some(evicted) = self.pop_min() {
+                    self.observer.on_evict(evicted);
+                }
+            } else {
+                self.observer.on_reject(item);
+                return Ok(());
+            }
+        };
+
+        if let Err(source) = self.try_grow() {
+            return Err(AllocError { item, source });
+        }
+        self.observer.on_insert(&item);
+        self.queue.push(item);
+        self.weight = total;
+        while self.queue.len() > self.capacity || self.weight > self.budget {
+            if let Some(evicted) = self.pop_min() {
+                self.observer.on_evict(evicted);
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes room in the heap for one more item, doubling its allocation
+    /// when it's full, but not past the queue's capacity.
+    fn try_grow(&mut self) -> Result<(), TryReserveError> {
+        const MIN_ALLOCATION: usize = 4;
+
+        let len = self.queue.len();
+        if len < self.queue.capacity() {
+            return Ok(());
+        }
+        // A weighted queue briefly holds one item over its capacity.
+        let target = (len.saturating_mul(2).max(MIN_ALLOCATION))
+            .min(self.capacity)
+            .max(len + 1);
+        self.queue.try_reserve_exact(target - len)
+    }
+}
+
+/// A queue's weigher, shared between its clones.
+struct Weigher<T>(Arc<dyn Fn(&T) -> usize + Send + Sync>);
+
+impl<T> Clone for Weigher<T> {
+    fn clone(&self) -> Self {
+        Weigher(Arc::clone(&self.0))
+    }
+}
+
+impl<T> fmt::Debug for Weigher<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Weigher")
+    }
+}
+
+/// The error returned by [`TopQueue::try_new`] for a capacity too large to
+/// ever fit in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityError {
+    capacity: usize,
+}
+
+impl CapacityError {
+    /// Returns the capacity that was asked for.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "a capacity of {} items can't fit in memory",
+            self.capacity
+        )
+    }
+}
+
+impl Error for CapacityError {}
+
+/// The error returned by [`TopQueue::try_push`] when the queue couldn't
+/// allocate memory for the item, which it holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocError<T> {
+    item: T,
+    source: TryReserveError,
+}
+
+impl<T> AllocError<T> {
+    /// Returns the item that couldn't be pushed.
+    pub fn into_item(self) -> T {
+        self.item
+    }
+}
+
+impl<T> fmt::Display for AllocError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("couldn't allocate memory for the queue's items")
+    }
+}
+
+impl<T: fmt::Debug> Error for AllocError<T> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl<T: Ord, O: Observer<T>> TopQueue<T, O, MinMaxHeap<T>> {
+    /// Returns the largest item in the queue, in *O*(1).
+    #[must_use]
+    pub fn peek_max(&self) -> Option<&T> {
+        self.queue.peek_max()
+    }
+}
+
+/// Element types that [`TopQueue::push_batch`] can prefilter cheaply: the
+/// primitive integers, and `f64` via [`OrdF64`].
+///
+/// This trait is sealed and cannot be implemented outside this crate.
+pub trait Primitive: Ord + Copy + private::Sealed {}
+
+mod private {
+    pub trait Sealed {}
+}
+
+macro_rules! impl_primitive {
+    ($($t:ty),*) => {
+        $(
+            impl private::Sealed for $t {}
+            impl Primitive for $t {}
+        )*
+    };
+}
+
+impl_primitive!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, OrdF64);
+
+impl<T: Primitive, O: Observer<T>, H: Heap<T>> TopQueue<T, O, H> {
+    /// Pushes every item of `items`, in order, with the same result as calling
+    /// [`push`](TopQueue::push) on each.
+    ///
+    /// Once the queue is full, most items of a large input are smaller than
+    /// everything in it. Rather than checking those one at a time, this
+    /// compares each chunk of 64 items against the queue's current minimum in
+    /// a branch-free loop the compiler can vectorize, and only pushes the
+    /// items that pass.
+    ///
+    /// ```
+    /// # use topqueue::topqueue_final::TopQueue;
+    /// let mut q = TopQueue::new(3);
+    /// q.push_batch(&[5_u16, 1, 9, 3, 7, 2]);
+    /// assert_eq!(vec![5, 7, 9], q.into_vec());
+    /// ```
+    pub fn push_batch(&mut self, items: &[T]) {
+        const CHUNK: usize = u64::BITS as usize;
+
+        for chunk in items.chunks(CHUNK) {
+            let Some(threshold) = self.threshold() else {
+                for &item in chunk {
+                    self.push(item);
+                }
+                continue;
+            };
+
+            let mut survivors = 0_u64;
+            for (i, &item) in chunk.iter().enumerate() {
+                survivors |= u64::from(item >= threshold) << i;
+            }
+
+            // Walk the survivors in order, reporting the items between them
+            // as rejected. For the default observer those loops vanish.
+            let mut next = 0;
+            while survivors != 0 {
+                let i = survivors.trailing_zeros() as usize;
+                for &item in &chunk[next..i] {
+                    self.observer.on_reject(item);
+                }
+                self.push(chunk[i]);
+                next = i + 1;
+                survivors &= survivors - 1;
+            }
+            for &item in &chunk[next..] {
+                self.observer.on_reject(item);
+            }
+        }
+    }
+
+    /// Returns the smallest item an unweighted, full queue would still
+    /// admit, or `None` if the queue can't be prefiltered that way.
+    fn threshold(&self) -> Option<T> {
+        if self.weigher.is_some() || self.queue.len() < self.capacity {
+            return None;
         }
+        self.queue.peek_min().copied()
+    }
+}
+
+/// A draining iterator over the items of a `TopQueue`, in ascending order.
+///
+/// Created by [`TopQueue::drain_sorted`].
+#[derive(Debug)]
+pub struct DrainSorted<'a, T: Ord, H: Heap<T> = BinaryHeap<Reverse<T>>> {
+    queue: &'a mut H,
+    item: PhantomData<T>,
+}
+
+impl<T: Ord, H: Heap<T>> Iterator for DrainSorted<'_, T, H> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.queue.pop_min()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.queue.len();
+        (len, Some(len))
+    }
+}
+
+impl<T: Ord, H: Heap<T>> ExactSizeIterator for DrainSorted<'_, T, H> {}
+
+impl<T: Ord, H: Heap<T>> FusedIterator for DrainSorted<'_, T, H> {}
+
+impl<T: Ord, H: Heap<T>> Drop for DrainSorted<'_, T, H> {
+    fn drop(&mut self) {
+        self.queue.clear();
+    }
+}
+
+impl<T, O, H, O2, H2> PartialEq<TopQueue<T, O2, H2>> for TopQueue<T, O, H>
+where
+    T: Ord,
+    O: Observer<T>,
+    H: Heap<T>,
+    O2: Observer<T>,
+    H2: Heap<T>,
+{
+    /// Compares the queues' capacities, weight limits and items. Their
+    /// observers and weighers aren't compared.
+    fn eq(&self, other: &TopQueue<T, O2, H2>) -> bool {
+        self.capacity == other.capacity
+            && self.budget == other.budget
+            && self.max_item_weight == other.max_item_weight
+            && self.len() == other.len()
+            && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Ord, O: Observer<T>, H: Heap<T>> Eq for TopQueue<T, O, H> {}
+
+impl<T: Ord, O: Observer<T>, H: Heap<T>> Extend<T> for TopQueue<T, O, H> {
+    /// Pushes each item, in order.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|item| self.push(item));
+    }
+}
+
+impl<'a, T: Ord + Copy + 'a, O: Observer<T>, H: Heap<T>> Extend<&'a T> for TopQueue<T, O, H> {
+    /// Pushes a copy of each item, in order.
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|&item| self.push(item));
+    }
+}
+
+impl<T: Ord, O: Observer<T>, H: Heap<T>> IntoIterator for TopQueue<T, O, H> {
+    type Item = T;
+    type IntoIter = IntoIterSorted<T, H>;
+
+    /// Returns the items in ascending order, each popped from the heap in
+    /// *O*(log *n*) as it's needed.
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIterSorted::new(self.queue)
+    }
+}
+
+impl<'a, T: Ord, O: Observer<T>, H: Heap<T>> IntoIterator for &'a TopQueue<T, O, H> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    /// Returns the items in ascending order, as [`TopQueue::iter`] does.
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: Ord + fmt::Display, O: Observer<T>, H: Heap<T>> fmt::Display for TopQueue<T, O, H> {
+    /// Writes the items smallest first, like a list: `[4, 5, 7]`. Any
+    /// formatting options, such as a width, apply to each item.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        for (i, item) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            fmt::Display::fmt(item, f)?;
+        }
+        f.write_str("]")
+    }
+}
+
+/// An iterator over the items of a `TopQueue`, in ascending order.
+///
+/// Created by [`TopQueue::iter`].
+#[derive(Debug, Clone)]
+pub struct Iter<'a, T> {
+    items: std::vec::IntoIter<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.items.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.items.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.items.next_back()
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> FusedIterator for Iter<'_, T> {}
+
+/// A [`TopQueue`] of capacity `K`, which an iterator can be
+/// [`collect`](Iterator::collect)ed into.
+///
+/// ```
+/// # use topqueue::topqueue_final::{TopK, TopQueue};
+/// let top: TopK<_, 3> = [5, 1, 9, 3, 7].into_iter().collect();
+/// let q: TopQueue<_> = top.into_queue();
+/// assert_eq!(3, q.capacity());
+/// assert_eq!(vec![5, 7, 9], q.into_vec());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopK<T: Ord, const K: usize>(TopQueue<T>);
+
+impl<T: Ord, const K: usize> TopK<T, K> {
+    /// Creates an empty queue of capacity `K`.
+    #[must_use]
+    pub fn new() -> Self {
+        TopK(TopQueue::new(K))
+    }
+
+    /// Returns the underlying queue.
+    #[must_use]
+    pub fn into_queue(self) -> TopQueue<T> {
+        self.0
+    }
+}
+
+impl<T: Ord, const K: usize> Default for TopK<T, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord, const K: usize> FromIterator<T> for TopK<T, K> {
+    /// Pushes each item into a queue of capacity `K`.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        TopK(TopQueue::from_iter(K, iter))
+    }
+}
+
+impl<T: Ord, const K: usize> Extend<T> for TopK<T, K> {
+    /// Pushes each item, in order.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.0.extend(iter);
+    }
+}
+
+impl<T: Ord, const K: usize> From<TopK<T, K>> for TopQueue<T> {
+    fn from(top: TopK<T, K>) -> Self {
+        top.0
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::TopQueue;
-    use crate::util::rands;
+    use super::{TopK, TopQueue};
+    use crate::heap::MinMaxHeap;
+    use crate::observer::Counter;
+    use crate::util::{get_top, rands, OrdF64};
+
+    /// Checks that the queue holds no more than `capacity` items, and that
+    /// every parent in the underlying heap is no larger than its children.
+    fn assert_invariants<T: Ord + std::fmt::Debug>(q: &TopQueue<T>) {
+        assert!(q.len() <= q.capacity());
+        let heap = q.queue.as_slice();
+        for child in 1..heap.len() {
+            let parent = (child - 1) / 2;
+            assert!(heap[parent].0 <= heap[child].0, "heap violated: {heap:?}");
+        }
+    }
 
     #[test]
     fn topq_can_be_empty() {
@@ -197,4 +970,362 @@ mod tests {
         let q = TopQueue::from_iter(5, text.chars());
         assert!(letters.ends_with(&q.into_vec()));
     }
+
+    #[test]
+    fn topq_remove_takes_out_one_matching_item() {
+        let mut q = TopQueue::from_iter(5, [4, 8, 2, 8, 6, 1]);
+        assert_eq!(Some(8), q.remove(&8));
+        assert_invariants(&q);
+        assert_eq!(4, q.len());
+        assert_eq!(None, q.remove(&1));
+        assert_invariants(&q);
+        assert_eq!(vec![2, 4, 6, 8], q.into_vec());
+    }
+
+    #[test]
+    fn topq_retain_filters_items() {
+        let mut q = TopQueue::from_iter(6, 1..=10);
+        q.retain(|n| n % 2 == 0);
+        assert_invariants(&q);
+        assert_eq!(6, q.capacity());
+        assert_eq!(vec![6, 8, 10], q.into_vec());
+    }
+
+    #[test]
+    fn topq_drain_sorted_empties_queue_in_order() {
+        let mut q = TopQueue::from_iter(4, [5, 3, 9, 1, 7]);
+        let drain = q.drain_sorted();
+        assert_eq!(4, drain.len());
+        assert_eq!(vec![3, 5, 7, 9], drain.collect::<Vec<_>>());
+        assert_invariants(&q);
+        assert!(q.is_empty());
+
+        // A partially consumed drain still empties the queue.
+        for n in [2, 4, 6] {
+            q.push(n);
+        }
+        assert_eq!(Some(2), q.drain_sorted().next());
+        assert!(q.is_empty());
+        assert_eq!(4, q.capacity());
+    }
+
+    #[test]
+    fn topq_clear_keeps_capacity() {
+        let mut q = TopQueue::from_iter(3, [1, 2, 3, 4]);
+        q.clear();
+        assert_invariants(&q);
+        assert!(q.is_empty());
+        assert_eq!(3, q.capacity());
+
+        for n in [9, 8, 7, 6] {
+            q.push(n);
+            assert_invariants(&q);
+        }
+        assert_eq!(vec![7, 8, 9], q.into_vec());
+    }
+
+    #[test]
+    fn topq_set_capacity_evicts_smallest_in_order() {
+        let mut q = TopQueue::from_iter(6, [3, 8, 1, 6, 4, 9]);
+        assert_eq!(vec![1, 3, 4], q.set_capacity(3));
+        assert_invariants(&q);
+        assert_eq!(3, q.capacity());
+
+        // Shrinking an under-full queue evicts nothing.
+        assert!(q.set_capacity(3).is_empty());
+        assert!(q.set_capacity(10).is_empty());
+        assert_eq!(3, q.len());
+        assert_eq!(10, q.capacity());
+
+        for n in [2, 5, 7] {
+            q.push(n);
+        }
+        q.shrink_to_fit();
+        assert_invariants(&q);
+        assert_eq!(vec![2, 5, 6, 7, 8, 9], q.into_vec());
+    }
+
+    #[test]
+    fn topq_set_capacity_to_zero_evicts_everything() {
+        let mut q = TopQueue::from_iter(3, ['a', 'c', 'b']);
+        assert_eq!(vec!['a', 'b', 'c'], q.set_capacity(0));
+        q.push('z');
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn topq_of_capacity_zero_rejects_everything() {
+        let mut q = TopQueue::new(0).with_observer(Counter::default());
+        q.push_batch(&[3, 1, 2]);
+        assert!(q.try_push(4).is_ok());
+        assert!(q.is_empty());
+        assert_eq!(0, q.queue.capacity());
+        assert_eq!((0, 4), (q.observer().inserted, q.observer().rejected));
+
+        let mut q = TopQueue::with_weigher(0, 10, |_: &i32| 1);
+        q.push(1);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn topq_allocates_lazily_up_to_capacity() {
+        let mut q = TopQueue::new(usize::MAX);
+        assert_eq!(0, q.queue.capacity());
+        for n in 0..10 {
+            q.push(n);
+        }
+        assert_eq!(16, q.queue.capacity());
+
+        let mut q = TopQueue::new(5);
+        for n in 0..100 {
+            q.push(n);
+        }
+        assert_eq!(5, q.queue.capacity());
+        assert_invariants(&q);
+    }
+
+    #[test]
+    fn topq_try_new_checks_capacity_without_allocating() {
+        let mut q = TopQueue::try_new(1 << 30).unwrap();
+        assert_eq!(0, q.queue.capacity());
+        for n in 0..1000 {
+            q.try_push(n).unwrap();
+        }
+        assert!(q.queue.capacity() < 2000);
+        assert_eq!(0, q.into_vec()[0]);
+
+        let err = TopQueue::<i32>::try_new(usize::MAX).unwrap_err();
+        assert_eq!(usize::MAX, err.capacity());
+        assert_eq!(
+            format!("a capacity of {} items can't fit in memory", usize::MAX),
+            err.to_string()
+        );
+        // Items that take no space always fit.
+        assert!(TopQueue::<()>::try_new(usize::MAX).is_ok());
+    }
+
+    #[test]
+    fn topq_weighted_evicts_smallest_to_fit_budget() {
+        let mut q = TopQueue::with_weigher(usize::MAX, 10, Vec::<u8>::len);
+        assert_eq!(Some(10), q.weight_budget());
+        assert_eq!(Some(10), q.max_item_weight());
+
+        q.push(vec![5; 4]);
+        q.push(vec![3; 3]);
+        q.push(vec![7; 2]);
+        assert_eq!(9, q.total_weight());
+        assert_invariants(&q);
+
+        // Admitting a large item evicts the smallest until the budget fits.
+        q.push(vec![9; 5]);
+        assert_eq!(7, q.total_weight());
+        assert_invariants(&q);
+        assert_eq!(vec![vec![7; 2], vec![9; 5]], q.into_vec());
+    }
+
+    #[test]
+    fn topq_weighted_rejects_oversized_items() {
+        let mut q = TopQueue::with_weigher(usize::MAX, 10, String::len).with_max_item_weight(4);
+        assert_eq!(Some(4), q.max_item_weight());
+        q.push("zzzzz".to_string());
+        q.push("b".to_string());
+        assert_eq!(1, q.len());
+        assert_eq!(1, q.total_weight());
+
+        // An item over the whole budget never fits, even in an empty queue.
+        let mut q = TopQueue::with_weigher(usize::MAX, 3, String::len);
+        q.push("toolong".to_string());
+        assert!(q.is_empty());
+        assert_eq!(0, q.total_weight());
+    }
+
+    #[test]
+    fn topq_weighted_accepts_capturing_weighers() {
+        let sizes = std::collections::HashMap::from([("a", 4), ("b", 3), ("c", 5)]);
+        let mut q = TopQueue::with_weigher(usize::MAX, 8, move |s: &&str| sizes[s]);
+        for s in ["a", "b", "c"] {
+            q.push(s);
+        }
+        assert_eq!(8, q.total_weight());
+        assert_eq!(vec!["b", "c"], q.clone().into_vec());
+    }
+
+    #[test]
+    fn topq_weighted_survives_overflowing_weights() {
+        let half = usize::MAX / 2 + 1;
+        let mut q = TopQueue::with_weigher(usize::MAX, usize::MAX, |n: &usize| *n);
+        q.push(half);
+        q.push(half + 1);
+        assert_eq!(half + 1, q.total_weight());
+        // A smaller item that would overflow the total is turned away.
+        q.push(half);
+        assert_eq!(vec![half + 1], q.clone().into_vec());
+        q.push(1);
+        assert_eq!(half + 2, q.total_weight());
+        q.push(usize::MAX);
+        assert_eq!(usize::MAX, q.total_weight());
+        assert_eq!(vec![usize::MAX], q.into_vec());
+    }
+
+    #[test]
+    fn topq_weighted_respects_capacity_too() {
+        let mut q = TopQueue::with_weigher(2, 100, |n: &u32| *n as usize);
+        for n in [10, 30, 20] {
+            q.push(n);
+        }
+        assert_eq!(50, q.total_weight());
+        assert_eq!(vec![20, 30], q.into_vec());
+    }
+
+    #[test]
+    fn topq_weighted_tracks_weight_through_removals() {
+        let mut q = TopQueue::with_weigher(usize::MAX, 100, |n: &u32| *n as usize);
+        for n in [5, 10, 15, 20, 25] {
+            q.push(n);
+        }
+        assert_eq!(75, q.total_weight());
+        q.remove(&10);
+        assert_eq!(65, q.total_weight());
+        q.retain(|n| *n != 20);
+        assert_eq!(45, q.total_weight());
+        assert_eq!(vec![5], q.set_capacity(2));
+        assert_eq!(40, q.total_weight());
+        q.drain_sorted().next();
+        assert_eq!(0, q.total_weight());
+        q.push(3);
+        q.clear();
+        assert_eq!(0, q.total_weight());
+        assert_invariants(&q);
+    }
+
+    #[test]
+    fn topq_unweighted_has_no_budget() {
+        let q = TopQueue::from_iter(3, [1, 2, 3]);
+        assert_eq!(None, q.weight_budget());
+        assert_eq!(None, q.max_item_weight());
+        assert_eq!(0, q.total_weight());
+    }
+
+    #[test]
+    fn topq_page_returns_rank_window() {
+        let nums: Vec<i32> = rands().take(1000).collect();
+        let q = TopQueue::from_iter(100, nums.iter().copied());
+        let ranked = get_top(&nums, 100);
+        for (offset, limit) in [(0, 10), (10, 10), (95, 10), (0, 100), (37, 1)] {
+            let end = (offset + limit).min(100);
+            let page: Vec<i32> = q.page(offset, limit).into_iter().copied().collect();
+            assert_eq!(ranked[offset..end], page[..]);
+        }
+        assert!(q.page(100, 1).is_empty());
+        assert!(q.page(5, 0).is_empty());
+        assert!(q.page(usize::MAX, usize::MAX).is_empty());
+        assert_eq!(100, q.page(0, usize::MAX).len());
+    }
+
+    #[test]
+    fn topq_page_keeps_ties_together() {
+        let q = TopQueue::from_iter(6, [3, 7, 7, 5, 7, 3, 1]);
+        assert_eq!(vec![&7, &7], q.page(0, 2));
+        assert_eq!(vec![&7, &5, &3], q.page(2, 3));
+        assert_eq!(vec![&3], q.page(5, 3));
+    }
+
+    #[test]
+    fn topq_push_batch_matches_push() {
+        let nums: Vec<i32> = rands().take(10_000).collect();
+        for capacity in [0, 1, 10, 100, 20_000] {
+            let mut batched = TopQueue::new(capacity);
+            batched.push_batch(&nums);
+            assert_invariants(&batched);
+            let pushed = TopQueue::from_iter(capacity, nums.iter().copied());
+            assert_eq!(pushed.into_vec(), batched.into_vec());
+        }
+    }
+
+    #[test]
+    fn topq_push_batch_handles_floats_and_observers() {
+        let nums: Vec<OrdF64> = rands().take(1000).map(OrdF64).collect();
+        let mut q = TopQueue::new(10).with_observer(Counter::default());
+        q.push_batch(&nums);
+        let counts = *q.observer();
+        assert_eq!(1000, counts.inserted + counts.rejected);
+        assert_eq!(counts.inserted - 10, counts.evicted);
+
+        let mut expected = nums;
+        expected.sort_unstable();
+        assert_eq!(expected[990..], q.drain_sorted().collect::<Vec<_>>()[..]);
+    }
+
+    #[test]
+    fn topq_clones_items_and_observer() {
+        let mut q = TopQueue::from_iter(3, [4, 8, 1]).with_observer(Counter::default());
+        q.push(6);
+        let mut copy = q.clone();
+        copy.push(9);
+        assert_eq!(vec![4, 6, 8], q.clone().into_vec());
+        assert_eq!(vec![6, 8, 9], copy.into_vec());
+        assert_eq!(1, q.observer().inserted);
+    }
+
+    #[test]
+    fn topq_equality_ignores_layout_and_observer() {
+        let a = TopQueue::from_iter(4, [1, 2, 3, 4, 5]);
+        let b = TopQueue::from_iter(4, [5, 4, 3, 2]).with_observer(Counter::default());
+        assert_eq!(a, b);
+        assert_eq!(b.clone().with_heap::<MinMaxHeap<_>>(), a);
+        assert_ne!(a, TopQueue::from_iter(5, [2, 3, 4, 5]));
+        assert_ne!(a, TopQueue::from_iter(4, [2, 3, 4, 4]));
+        assert_ne!(a, TopQueue::with_weigher(4, 10, |_: &i32| 1));
+    }
+
+    #[test]
+    fn topq_extends_by_value_and_reference() {
+        let mut q = TopQueue::new(3).with_observer(Counter::default());
+        q.extend([5, 1, 9]);
+        q.extend(&[3, 7]);
+        assert_eq!(5, q.observer().inserted + q.observer().rejected);
+        assert_eq!(vec![5, 7, 9], q.into_vec());
+    }
+
+    #[test]
+    fn topq_iterates_smallest_first() {
+        let q = TopQueue::from_iter(4, ["pear", "fig", "apple", "plum", "kiwi"]);
+        assert_eq!(
+            vec![&"fig", &"kiwi", &"pear", &"plum"],
+            q.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(&"plum"), q.iter().next_back());
+        assert_eq!(Some(&"fig"), q.peek_min());
+        assert_eq!(4, (&q).into_iter().len());
+
+        let mut seen = Vec::new();
+        for word in q {
+            seen.push(word);
+        }
+        assert_eq!(vec!["fig", "kiwi", "pear", "plum"], seen);
+    }
+
+    #[test]
+    fn topq_displays_items_smallest_first() {
+        let q = TopQueue::from_iter(3, [25, 3, 10, 400]);
+        assert_eq!("[10, 25, 400]", q.to_string());
+        assert_eq!("[ 10,  25, 400]", format!("{q:>3}"));
+        assert_eq!("[]", TopQueue::<i32>::new(3).to_string());
+    }
+
+    #[test]
+    fn topk_collects_and_extends() {
+        let nums: Vec<i32> = rands().take(1000).collect();
+        let mut top: TopK<_, 10> = nums.iter().copied().collect();
+        assert_eq!(
+            TopQueue::from_iter(10, nums.iter().copied()),
+            top.clone().into_queue()
+        );
+
+        top.extend([i32::MAX]);
+        let q = TopQueue::from(top);
+        assert_eq!(10, q.capacity());
+        assert_eq!(Some(&i32::MAX), q.iter().next_back());
+        assert!(TopK::<u8, 0>::default().into_queue().is_empty());
+    }
 }
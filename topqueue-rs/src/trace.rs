@@ -0,0 +1,618 @@
+//! Recording what was done to a `TopQueue`, and replaying it.
+//!
+//! A [`Recorder`] is an [`Observer`] that writes every push and capacity
+//! change to a compact trace, along with snapshots of the queue's contents
+//! taken with [`TopQueue::record_snapshot`]. A [`Trace`] read back from it
+//! rebuilds the queue with [`Trace::replay`], which checks each snapshot
+//! along the way, and [`Trace::bisect`] finds the first operation after
+//! which the queue disagrees with a reference, like [`sorted_oracle`].
+//!
+//! ```
+//! # use topqueue::trace::{record, sorted_oracle, Trace};
+//! let mut q = record::<i64>(3);
+//! for n in [5, 1, 9, 7] {
+//!     q.push(n);
+//! }
+//! q.record_snapshot();
+//! q.set_capacity(2);
+//!
+//! let bytes = q.into_observer().finish().unwrap();
+//! let trace = Trace::<i64>::read_from(&bytes[..]).unwrap();
+//! assert_eq!(vec![7, 9], trace.replay().unwrap().into_vec());
+//! assert_eq!(None, trace.bisect(sorted_oracle));
+//! ```
+//!
+//! Traces only hold pushes and capacity changes, so queues that are also
+//! weighted, or changed by `remove`, `retain` or `clear`, replay differently
+//! from how they ran. Their snapshots will show where.
+//!
+//! ## Format
+//!
+//! A trace starts with the bytes `TQT1`, the item type's name as a length and
+//! UTF-8 bytes, and the initial capacity. Then each operation is a tag byte
+//! followed by its arguments: `0` and an item for a push, `1` and the new
+//! capacity, or `2`, a count and that many items, smallest first, for a
+//! snapshot. Integers are LEB128 varints, signed ones zigzag-encoded first,
+//! and `f64`s their 8 little-endian bytes.
+
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+use crate::observer::Observer;
+use crate::topqueue_final::TopQueue;
+use crate::util::OrdF64;
+
+const MAGIC: &[u8; 4] = b"TQT1";
+const PUSH: u8 = 0;
+const SET_CAPACITY: u8 = 1;
+const SNAPSHOT: u8 = 2;
+
+/// An item that can be written to a trace.
+pub trait TraceItem: Ord + Clone {
+    /// The item type's name, recorded in the trace so it can't be read back
+    /// as the wrong type.
+    const NAME: &'static str;
+
+    /// Appends the item's encoding to `out`.
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Decodes an item from the front of `bytes`, advancing past it.
+    fn decode(bytes: &mut &[u8]) -> Option<Self>;
+}
+
+macro_rules! impl_unsigned {
+    ($($t:ty),*) => {$(
+        impl TraceItem for $t {
+            const NAME: &'static str = stringify!($t);
+
+            fn encode(&self, out: &mut Vec<u8>) {
+                write_varint(out, u64::from(*self));
+            }
+
+            fn decode(bytes: &mut &[u8]) -> Option<Self> {
+                read_varint(bytes)?.try_into().ok()
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_signed {
+    ($($t:ty),*) => {$(
+        impl TraceItem for $t {
+            const NAME: &'static str = stringify!($t);
+
+            fn encode(&self, out: &mut Vec<u8>) {
+                write_varint(out, zigzag(i64::from(*self)));
+            }
+
+            fn decode(bytes: &mut &[u8]) -> Option<Self> {
+                unzigzag(read_varint(bytes)?).try_into().ok()
+            }
+        }
+    )*};
+}
+
+impl_unsigned!(u8, u16, u32, u64);
+impl_signed!(i8, i16, i32, i64);
+
+impl TraceItem for OrdF64 {
+    const NAME: &'static str = "f64";
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend(self.0.to_le_bytes());
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Option<Self> {
+        let (head, rest) = bytes.split_first_chunk()?;
+        *bytes = rest;
+        Some(OrdF64(f64::from_le_bytes(*head)))
+    }
+}
+
+// The casts reinterpret bits, which is the point of zigzag encoding.
+#[allow(clippy::cast_sign_loss)]
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+#[allow(clippy::cast_possible_wrap)]
+fn unzigzag(n: u64) -> i64 {
+    (n >> 1) as i64 ^ -((n & 1) as i64)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        // Only the low seven bits are kept.
+        #[allow(clippy::cast_possible_truncation)]
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    out.push(n as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut n = 0_u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        n |= u64::from(byte & 0x7f).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) {
+    write_varint(out, len as u64);
+}
+
+fn read_len(bytes: &mut &[u8]) -> Option<usize> {
+    read_varint(bytes)?.try_into().ok()
+}
+
+/// Returns the name of the item type a trace holds, or `None` if `bytes`
+/// don't start with a trace.
+///
+/// This is [`TraceItem::NAME`] of the recorded items, for picking the type to
+/// read the trace as.
+#[must_use]
+pub fn item_type(mut bytes: &[u8]) -> Option<&str> {
+    read_header(&mut bytes)
+}
+
+fn read_header<'a>(bytes: &mut &'a [u8]) -> Option<&'a str> {
+    *bytes = bytes.strip_prefix(MAGIC)?;
+    let len = read_len(bytes)?;
+    let (name, rest) = bytes.split_at_checked(len)?;
+    *bytes = rest;
+    std::str::from_utf8(name).ok()
+}
+
+/// One recorded operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op<T> {
+    /// An item was pushed.
+    Push(T),
+    /// The capacity was set.
+    SetCapacity(usize),
+    /// The queue held these items, smallest first.
+    Snapshot(Vec<T>),
+}
+
+/// An [`Observer`] that writes a trace of a queue's pushes and capacity
+/// changes to `W`.
+///
+/// Writing errors can't interrupt a push, so the first one is kept, further
+/// writes are skipped, and it's returned by [`finish`](Recorder::finish).
+#[derive(Debug)]
+pub struct Recorder<T, W: Write = Vec<u8>> {
+    out: W,
+    buf: Vec<u8>,
+    error: Option<io::Error>,
+    item: PhantomData<fn(T)>,
+}
+
+/// Creates an empty queue of capacity `capacity` that records itself in
+/// memory.
+#[must_use]
+pub fn record<T: TraceItem>(capacity: usize) -> TopQueue<T, Recorder<T>> {
+    TopQueue::new(capacity).with_observer(Recorder::new(capacity))
+}
+
+impl<T: TraceItem> Recorder<T> {
+    /// Creates a recorder, writing to memory, for a queue of capacity
+    /// `capacity`.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Recorder::to_writer(Vec::new(), capacity)
+    }
+}
+
+impl<T: TraceItem, W: Write> Recorder<T, W> {
+    /// Creates a recorder, writing to `out`, for a queue of capacity
+    /// `capacity`.
+    ///
+    /// Each operation is written as it happens, so wrap a file in a
+    /// [`BufWriter`](std::io::BufWriter).
+    pub fn to_writer(out: W, capacity: usize) -> Self {
+        let mut recorder = Recorder {
+            out,
+            buf: Vec::new(),
+            error: None,
+            item: PhantomData,
+        };
+        recorder.buf.extend(MAGIC);
+        write_len(&mut recorder.buf, T::NAME.len());
+        recorder.buf.extend(T::NAME.as_bytes());
+        write_len(&mut recorder.buf, capacity);
+        recorder.flush_op();
+        recorder
+    }
+
+    /// Flushes the trace and returns the writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error met while writing.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn flush_op(&mut self) {
+        if self.error.is_none() {
+            if let Err(err) = self.out.write_all(&self.buf) {
+                self.error = Some(err);
+            }
+        }
+        self.buf.clear();
+    }
+
+    fn record_push(&mut self, item: &T) {
+        self.buf.push(PUSH);
+        item.encode(&mut self.buf);
+        self.flush_op();
+    }
+
+    fn record_snapshot<'a>(&mut self, items: impl ExactSizeIterator<Item = &'a T>)
+    where
+        T: 'a,
+    {
+        self.buf.push(SNAPSHOT);
+        write_len(&mut self.buf, items.len());
+        for item in items {
+            item.encode(&mut self.buf);
+        }
+        self.flush_op();
+    }
+}
+
+impl<T: TraceItem, W: Write> Observer<T> for Recorder<T, W> {
+    fn on_insert(&mut self, item: &T) {
+        self.record_push(item);
+    }
+
+    fn on_reject(&mut self, item: T) {
+        self.record_push(&item);
+    }
+
+    fn on_set_capacity(&mut self, capacity: usize) {
+        self.buf.push(SET_CAPACITY);
+        write_len(&mut self.buf, capacity);
+        self.flush_op();
+    }
+}
+
+impl<T: TraceItem, W: Write> TopQueue<T, Recorder<T, W>> {
+    /// Records the queue's contents in its trace, for [`Trace::replay`] to
+    /// check against.
+    pub fn record_snapshot(&mut self) {
+        let items = self.page(0, self.len()).into_iter().rev().cloned();
+        let items: Vec<T> = items.collect();
+        self.observer_mut().record_snapshot(items.iter());
+    }
+}
+
+/// A recorded trace, read back from a [`Recorder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace<T> {
+    /// The queue's initial capacity.
+    pub capacity: usize,
+    /// What was done to the queue, in order.
+    pub ops: Vec<Op<T>>,
+}
+
+/// The first snapshot in a trace that its replay didn't match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch<T> {
+    /// The index of the snapshot in [`Trace::ops`].
+    pub op: usize,
+    /// The recorded contents, smallest first.
+    pub expected: Vec<T>,
+    /// The replayed contents, smallest first.
+    pub actual: Vec<T>,
+}
+
+impl<T: TraceItem> Trace<T> {
+    /// Reads a whole trace from `reader`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if reading fails, or with [`io::ErrorKind::InvalidData`] if the
+    /// trace is malformed, truncated, or of items other than `T`.
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Trace::decode(&bytes).map_err(|msg| io::Error::new(io::ErrorKind::InvalidData, msg))
+    }
+
+    fn decode(mut bytes: &[u8]) -> Result<Self, String> {
+        let bytes = &mut bytes;
+        let truncated = || "trace is truncated or corrupt".to_string();
+        let name = read_header(bytes).ok_or("not a trace")?;
+        if name != T::NAME {
+            return Err(format!("trace is of {name}, not {}", T::NAME));
+        }
+        let capacity = read_len(bytes).ok_or_else(truncated)?;
+
+        let mut ops = Vec::new();
+        while let Some((&tag, rest)) = bytes.split_first() {
+            *bytes = rest;
+            let op = match tag {
+                PUSH => T::decode(bytes).map(Op::Push),
+                SET_CAPACITY => read_len(bytes).map(Op::SetCapacity),
+                SNAPSHOT => read_len(bytes).and_then(|len| {
+                    (0..len)
+                        .map(|_| T::decode(bytes))
+                        .collect::<Option<_>>()
+                        .map(Op::Snapshot)
+                }),
+                _ => return Err(format!("unknown operation {tag}")),
+            };
+            ops.push(op.ok_or_else(truncated)?);
+        }
+        Ok(Trace { capacity, ops })
+    }
+
+    /// Rebuilds the queue from the whole trace, checking each snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first snapshot that doesn't match the rebuilt queue.
+    pub fn replay(&self) -> Result<TopQueue<T>, Box<Mismatch<T>>> {
+        let mut queue = TopQueue::new(self.capacity);
+        for (i, op) in self.ops.iter().enumerate() {
+            if let Op::Snapshot(expected) = op {
+                let actual = contents(&queue);
+                if &actual != expected {
+                    let expected = expected.clone();
+                    return Err(Box::new(Mismatch {
+                        op: i,
+                        expected,
+                        actual,
+                    }));
+                }
+            } else {
+                apply(&mut queue, op);
+            }
+        }
+        Ok(queue)
+    }
+
+    /// Rebuilds the queue from the first `len` operations, without checking
+    /// snapshots.
+    #[must_use]
+    pub fn replay_prefix(&self, len: usize) -> TopQueue<T> {
+        let mut queue = TopQueue::new(self.capacity);
+        for op in &self.ops[..len.min(self.ops.len())] {
+            apply(&mut queue, op);
+        }
+        queue
+    }
+
+    /// Returns the index of the first operation after which the queue's
+    /// contents differ from what `oracle` expects, or `None` if they agree at
+    /// the end of the trace.
+    ///
+    /// `oracle` is given the initial capacity and a prefix of the operations,
+    /// and returns the items a queue should then hold, in any order. The
+    /// search takes a logarithmic number of replays, so it assumes that once
+    /// the queue and the oracle disagree, they keep disagreeing.
+    pub fn bisect(&self, mut oracle: impl FnMut(usize, &[Op<T>]) -> Vec<T>) -> Option<usize> {
+        let mut agrees = |len: usize| {
+            let mut expected = oracle(self.capacity, &self.ops[..len]);
+            expected.sort_unstable();
+            contents(&self.replay_prefix(len)) == expected
+        };
+        if agrees(self.ops.len()) {
+            return None;
+        }
+        // The empty prefix always agrees; the whole trace doesn't.
+        let (mut good, mut bad) = (0, self.ops.len());
+        while bad - good > 1 {
+            let mid = good + (bad - good) / 2;
+            if agrees(mid) {
+                good = mid;
+            } else {
+                bad = mid;
+            }
+        }
+        Some(bad - 1)
+    }
+}
+
+/// A reference for [`Trace::bisect`]: the largest items pushed, found by
+/// sorting as [`util::get_top`](crate::util::get_top) does, and cut down by
+/// each capacity change.
+#[must_use]
+pub fn sorted_oracle<T: Ord + Clone>(capacity: usize, ops: &[Op<T>]) -> Vec<T> {
+    let top = |mut items: Vec<T>, k: usize| {
+        items.sort_unstable_by(|a, b| b.cmp(a));
+        items.truncate(k);
+        items
+    };
+    let mut capacity = capacity;
+    let mut items = Vec::new();
+    for op in ops {
+        match op {
+            Op::Push(item) => items.push(item.clone()),
+            Op::SetCapacity(new) => {
+                capacity = *new;
+                items = top(items, capacity);
+            }
+            Op::Snapshot(_) => {}
+        }
+    }
+    top(items, capacity)
+}
+
+fn apply<T: Ord + Clone>(queue: &mut TopQueue<T>, op: &Op<T>) {
+    match op {
+        Op::Push(item) => queue.push(item.clone()),
+        Op::SetCapacity(capacity) => {
+            queue.set_capacity(*capacity);
+        }
+        Op::Snapshot(_) => {}
+    }
+}
+
+fn contents<T: Ord + Clone>(queue: &TopQueue<T>) -> Vec<T> {
+    let mut items: Vec<T> = queue.page(0, queue.len()).into_iter().cloned().collect();
+    items.reverse();
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{item_type, record, sorted_oracle, Op, Recorder, Trace, TraceItem};
+    use crate::topqueue_final::TopQueue;
+    use crate::util::{get_top, rands, OrdF64};
+
+    fn round_trip<T: TraceItem>(q: TopQueue<T, Recorder<T>>) -> Trace<T> {
+        let bytes = q.into_observer().finish().unwrap();
+        Trace::read_from(&bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn traces_round_trip() {
+        let mut q = record(2);
+        for n in [i64::MIN, -1, 0, 300, i64::MAX] {
+            q.push(n);
+        }
+        q.record_snapshot();
+        q.set_capacity(5);
+        let trace = round_trip(q);
+        assert_eq!(2, trace.capacity);
+        assert_eq!(
+            vec![
+                Op::Push(i64::MIN),
+                Op::Push(-1),
+                Op::Push(0),
+                Op::Push(300),
+                Op::Push(i64::MAX),
+                Op::Snapshot(vec![300, i64::MAX]),
+                Op::SetCapacity(5),
+            ],
+            trace.ops
+        );
+
+        let mut q = record(1);
+        q.push(OrdF64(-2.5));
+        let trace = round_trip(q);
+        assert_eq!(vec![Op::Push(OrdF64(-2.5))], trace.ops);
+    }
+
+    #[test]
+    fn traces_are_compact() {
+        let mut q = record::<u32>(10);
+        for n in 0..1000 {
+            q.push(n % 100);
+        }
+        let bytes = q.into_observer().finish().unwrap();
+        // A tag and a one-byte varint per push, and a small header.
+        assert!(bytes.len() < 2010, "{} bytes", bytes.len());
+    }
+
+    #[test]
+    fn traces_reject_bad_input() {
+        let bytes = record::<i32>(3).into_observer().finish().unwrap();
+        assert!(Trace::<i32>::read_from(&bytes[..]).is_ok());
+        assert!(Trace::<i64>::read_from(&bytes[..]).is_err());
+        assert!(Trace::<i32>::read_from(&b"nope"[..]).is_err());
+        assert_eq!(Some("i32"), item_type(&bytes));
+        assert_eq!(None, item_type(b"nope"));
+
+        let mut q = record::<i32>(3);
+        q.push(1_000_000);
+        let bytes = q.into_observer().finish().unwrap();
+        assert!(Trace::<i32>::read_from(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn replay_rebuilds_and_checks_snapshots() {
+        let nums: Vec<i32> = rands().take(500).collect();
+        let mut q = record(20);
+        for (i, &n) in nums.iter().enumerate() {
+            q.push(n);
+            if i % 100 == 0 {
+                q.record_snapshot();
+            }
+            if i == 250 {
+                q.set_capacity(10);
+            }
+        }
+        let expected = q.page(0, 10).into_iter().copied().collect::<Vec<_>>();
+        let trace = round_trip(q);
+        let replayed = trace.replay().unwrap();
+        assert_eq!(
+            expected,
+            replayed
+                .page(0, 10)
+                .into_iter()
+                .copied()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(None, trace.bisect(sorted_oracle));
+    }
+
+    #[test]
+    fn weighted_snapshots_report_mismatches() {
+        // Weights aren't recorded, so a queue that evicted by weight doesn't
+        // replay the same, and its snapshots say so.
+        let cases: [(usize, usize, &[i32], &[i32]); 3] = [
+            (10, 5, &[1, 2, 3], &[2, 3]),
+            (5, 2, &[1, 2, 3], &[2]),
+            (4, 3, &[4, 1, 5, 2], &[1, 2]),
+        ];
+        for (capacity, budget, pushes, kept) in cases {
+            let weigher = |n: &i32| n.unsigned_abs() as usize;
+            let mut q = TopQueue::with_weigher(capacity, budget, weigher)
+                .with_observer(Recorder::new(capacity));
+            for &n in pushes {
+                q.push(n);
+            }
+            q.record_snapshot();
+            let mismatch = round_trip(q).replay().unwrap_err();
+            let mut replayed = pushes.to_vec();
+            replayed.sort_unstable();
+            assert_eq!(
+                (pushes.len(), kept.to_vec(), replayed),
+                (mismatch.op, mismatch.expected, mismatch.actual)
+            );
+        }
+    }
+
+    #[test]
+    fn bisect_finds_first_divergence() {
+        let nums: Vec<i32> = rands().take(300).collect();
+        let mut q = record(10);
+        for &n in &nums[..200] {
+            q.push(n);
+        }
+        q.set_capacity(5);
+        for &n in &nums[200..] {
+            q.push(n);
+        }
+        let trace = round_trip(q);
+
+        // get_top over every push so far, ignoring capacity changes, agrees
+        // until the queue shrinks and forgets items.
+        let get_top_oracle = |capacity, ops: &[Op<i32>]| {
+            let pushed: Vec<i32> = ops
+                .iter()
+                .filter_map(|op| match op {
+                    Op::Push(n) => Some(*n),
+                    _ => None,
+                })
+                .collect();
+            get_top(&pushed, capacity)
+        };
+        assert_eq!(Some(200), trace.bisect(get_top_oracle));
+        assert_eq!(None, trace.bisect(sorted_oracle));
+    }
+}
@@ -0,0 +1,308 @@
+//! A "trending" top-k, where each key's score decays exponentially with age.
+//!
+//! Every event `(key, weight, timestamp)` adds to its key's score, and an
+//! event's contribution halves every `half_life` seconds. A spike from last
+//! week therefore can't outrank a steady stream from the last hour.
+//!
+//! This uses forward decay[^forward]: rather than shrinking every stored score
+//! as time passes, each event is stored scaled *up* by `2^((t - L) / half_life)`
+//! relative to a fixed landmark time `L`. Since every score is scaled by the
+//! same factor, the ranking never changes as time passes, and decayed scores
+//! are recovered by dividing by the current scale factor only when reported.
+//! The landmark is moved forward, and all scores rescaled, only when the scale
+//! factor would grow large enough to risk floating point overflow.
+//!
+//! ```
+//! # use topqueue::trending::{ManualClock, Trending};
+//! let clock = ManualClock::new(0.0);
+//! let mut trends = Trending::with_clock(60.0, &clock);
+//!
+//! trends.push("old-news", 100.0, 0.0);
+//! clock.set(600.0);
+//! trends.push("fresh", 1.0, 590.0);
+//!
+//! // Ten half-lives later, 100 has decayed below the newer event.
+//! let top = trends.top_k(1);
+//! assert_eq!("fresh", top[0].0);
+//! assert!((top[0].1 - 0.8909).abs() < 1e-4);
+//! ```
+//!
+//! [^forward]: Cormode, Shkapenyuk, Srivastava and Xu, "Forward Decay: A
+//!     Practical Time Decay Model for Streaming Systems", ICDE 2009.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::topqueue_final::TopQueue;
+use crate::util::OrdF64;
+
+/// Once the scale factor's exponent passes this, the landmark is moved.
+/// `2^512` is comfortably inside the range of an `f64`.
+const MAX_EXPONENT: f64 = 512.0;
+
+/// A source of the current time, in seconds since an arbitrary epoch.
+pub trait Clock {
+    /// Returns the current time in seconds.
+    fn now(&self) -> f64;
+}
+
+impl<C: Clock> Clock for &C {
+    fn now(&self) -> f64 {
+        (*self).now()
+    }
+}
+
+/// The system wall clock, in seconds since the Unix epoch.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |d| d.as_secs_f64())
+    }
+}
+
+/// A clock that only moves when told to, for tests and simulations.
+#[derive(Debug, Default, Clone)]
+pub struct ManualClock {
+    now: Cell<f64>,
+}
+
+impl ManualClock {
+    /// Creates a clock stopped at `now`.
+    #[must_use]
+    pub fn new(now: f64) -> Self {
+        ManualClock {
+            now: Cell::new(now),
+        }
+    }
+
+    /// Sets the current time.
+    pub fn set(&self, now: f64) {
+        self.now.set(now);
+    }
+
+    /// Moves the current time forward by `secs`.
+    pub fn advance(&self, secs: f64) {
+        self.now.set(self.now.get() + secs);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> f64 {
+        self.now.get()
+    }
+}
+
+/// Tracks exponentially decayed scores per key and reports the top `k`.
+#[derive(Debug)]
+pub struct Trending<K, C = SystemClock> {
+    half_life: f64,
+    landmark: f64,
+    scores: HashMap<K, f64>,
+    clock: C,
+}
+
+impl<K: Eq + Hash> Trending<K> {
+    /// Creates an empty tracker whose scores halve every `half_life` seconds,
+    /// using the system clock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `half_life` is not a positive, finite number.
+    #[must_use]
+    pub fn new(half_life: f64) -> Self {
+        Trending::with_clock(half_life, SystemClock)
+    }
+}
+
+impl<K: Eq + Hash, C: Clock> Trending<K, C> {
+    /// Creates an empty tracker whose scores halve every `half_life` seconds,
+    /// reading the current time from `clock`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `half_life` is not a positive, finite number.
+    #[must_use]
+    pub fn with_clock(half_life: f64, clock: C) -> Self {
+        assert!(
+            half_life.is_finite() && half_life > 0.0,
+            "half-life must be positive and finite, got {half_life}"
+        );
+        Trending {
+            half_life,
+            landmark: clock.now(),
+            scores: HashMap::new(),
+            clock,
+        }
+    }
+
+    /// Returns the half-life, in seconds.
+    #[must_use]
+    pub fn half_life(&self) -> f64 {
+        self.half_life
+    }
+
+    /// Returns the number of distinct keys seen.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    /// Returns true if no events have been recorded.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    /// Records an event for `key`, with the given `weight`, that happened at
+    /// `timestamp` seconds.
+    ///
+    /// Events may arrive out of order.
+    pub fn push(&mut self, key: K, weight: f64, timestamp: f64) {
+        if (timestamp - self.landmark) / self.half_life > MAX_EXPONENT {
+            self.move_landmark(timestamp);
+        }
+        *self.scores.entry(key).or_insert(0.0) += weight * self.scale(timestamp);
+    }
+
+    /// Records an event for `key`, with the given `weight`, happening now.
+    pub fn record(&mut self, key: K, weight: f64) {
+        let now = self.clock.now();
+        self.push(key, weight, now);
+    }
+
+    /// Returns the score of `key` decayed to the current time, or `None` if it
+    /// has never been seen.
+    #[must_use]
+    pub fn score(&self, key: &K) -> Option<f64> {
+        let scale = self.scale(self.clock.now());
+        self.scores.get(key).map(|s| s / scale)
+    }
+
+    /// Returns the `k` highest-scoring keys with their scores decayed to the
+    /// current time.
+    ///
+    /// As with [`TopQueue::into_vec`], results are ordered smallest first.
+    /// Keys with equal scores are ordered by key.
+    #[must_use]
+    pub fn top_k(&self, k: usize) -> Vec<(K, f64)>
+    where
+        K: Ord + Clone,
+    {
+        let scale = self.scale(self.clock.now());
+        let scored = self.scores.iter().map(|(key, &score)| (OrdF64(score), key));
+        TopQueue::from_iter(k, scored)
+            .into_vec()
+            .into_iter()
+            .map(|(OrdF64(score), key)| (key.clone(), score / scale))
+            .collect()
+    }
+
+    /// Returns the forward-decay scale factor for time `t`.
+    fn scale(&self, t: f64) -> f64 {
+        ((t - self.landmark) / self.half_life).exp2()
+    }
+
+    /// Rescales every stored score relative to a new landmark.
+    fn move_landmark(&mut self, landmark: f64) {
+        let rescale = ((self.landmark - landmark) / self.half_life).exp2();
+        for score in self.scores.values_mut() {
+            *score *= rescale;
+        }
+        self.landmark = landmark;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ManualClock, Trending, MAX_EXPONENT};
+
+    fn assert_close(expected: f64, actual: f64) {
+        assert!(
+            (expected - actual).abs() <= 1e-9 * expected.abs().max(1.0),
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn trending_scores_halve_every_half_life() {
+        let clock = ManualClock::new(1000.0);
+        let mut trends = Trending::with_clock(10.0, &clock);
+        trends.record('a', 8.0);
+        assert_close(8.0, trends.score(&'a').unwrap());
+
+        clock.advance(10.0);
+        assert_close(4.0, trends.score(&'a').unwrap());
+        clock.advance(20.0);
+        assert_close(1.0, trends.score(&'a').unwrap());
+        assert_eq!(None, trends.score(&'b'));
+    }
+
+    #[test]
+    fn trending_sums_events_per_key() {
+        let clock = ManualClock::new(0.0);
+        let mut trends = Trending::with_clock(5.0, &clock);
+        // Out-of-order events are fine.
+        trends.push("k", 1.0, 10.0);
+        trends.push("k", 2.0, 5.0);
+        clock.set(10.0);
+        assert_close(2.0, trends.score(&"k").unwrap());
+        assert_eq!(1, trends.len());
+    }
+
+    #[test]
+    fn trending_top_k_prefers_recent_activity() {
+        let clock = ManualClock::new(0.0);
+        let mut trends = Trending::with_clock(1.0, &clock);
+        trends.push("spike", 1000.0, 0.0);
+        for t in 0..20 {
+            trends.push("steady", 1.0, f64::from(t));
+            trends.push("quiet", 0.1, f64::from(t));
+        }
+        clock.set(19.0);
+
+        let top = trends.top_k(2);
+        let keys: Vec<_> = top.iter().map(|(k, _)| *k).collect();
+        assert_eq!(vec!["quiet", "steady"], keys);
+        assert_close(trends.score(&"steady").unwrap(), top[1].1);
+        assert_eq!(3, trends.top_k(10).len());
+    }
+
+    #[test]
+    fn trending_survives_moving_the_landmark() {
+        let clock = ManualClock::new(0.0);
+        let mut trends = Trending::with_clock(1.0, &clock);
+        trends.push(1, 1.0, 0.0);
+        trends.push(2, 1.0, 1.0);
+
+        // Far enough ahead that the scale factor would overflow without
+        // moving the landmark.
+        let later = 4.0 * MAX_EXPONENT;
+        trends.push(3, 1.0, later);
+        clock.set(later);
+        assert_close(1.0, trends.score(&3).unwrap());
+        assert_close(0.0, trends.score(&1).unwrap());
+
+        clock.set(later + 1.0);
+        assert_close(0.5, trends.score(&3).unwrap());
+        assert_eq!(
+            vec![3],
+            trends
+                .top_k(1)
+                .into_iter()
+                .map(|(k, _)| k)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "half-life must be positive")]
+    fn trending_rejects_bad_half_life() {
+        let _ = Trending::<u8>::new(0.0);
+    }
+}
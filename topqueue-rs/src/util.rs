@@ -37,11 +37,15 @@
 //! println!("{top100:?}: {}", type_of(&top100));
 //! ```
 
-use std::iter::from_fn;
+use std::cmp::{Ordering, Reverse};
+use std::iter::{from_fn, FusedIterator};
+use std::mem;
 
 use rand::distributions::{Distribution, Standard};
 use rand::{thread_rng, Rng};
 
+use crate::topqueue_final::TopQueue;
+
 /// Creates an infinite iterator of random numbers.
 pub fn rands<T>() -> impl Iterator<Item = T>
 where
@@ -55,10 +59,303 @@ where
 #[must_use]
 pub fn get_top(nums: &[i32], top: usize) -> Vec<i32> {
     let mut dupe = nums.to_vec();
-    dupe.sort_unstable_by(|a, b| b.cmp(a));
+    dupe.sort_unstable_by(|a, b| {
+        #[cfg(feature = "instrument")]
+        crate::instrument::count_comparison();
+        b.cmp(a)
+    });
     dupe.into_iter().take(top).collect()
 }
 
+/// An `f64` with a total order, so it can be stored in a `TopQueue`.
+///
+/// Values are compared with [`f64::total_cmp`], so `-0.0` sorts below `0.0`
+/// and NaNs sort beyond the infinities, by sign.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OrdF64(pub f64);
+
+impl PartialEq for OrdF64 {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OrdF64 {}
+
+impl PartialOrd for OrdF64 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrdF64 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// Returns the indices of the `k` largest items of `items`, largest first,
+/// without copying any of them.
+///
+/// Equal items are ranked by index, lowest first.
+///
+/// ```
+/// # use topqueue::util::argtop_k;
+/// assert_eq!(vec![4, 1, 5], argtop_k(&[3, 8, 1, 2, 9, 8], 3));
+/// ```
+#[must_use]
+pub fn argtop_k<T: Ord>(items: &[T], k: usize) -> Vec<usize> {
+    argtop_keys(items.iter(), k)
+}
+
+/// Returns the indices of the `k` items of `items` with the largest keys, as
+/// computed by `key`, largest first.
+///
+/// Equal keys are ranked by index, lowest first.
+///
+/// ```
+/// # use topqueue::util::argtop_k_by_key;
+/// let rows = [("ann", 31), ("bob", 45), ("cat", 27)];
+/// assert_eq!(vec![1, 0], argtop_k_by_key(&rows, 2, |row| row.1));
+/// ```
+pub fn argtop_k_by_key<T, K, F>(items: &[T], k: usize, key: F) -> Vec<usize>
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    argtop_keys(items.iter().map(key), k)
+}
+
+/// Returns references to the `k` largest items of `items`, largest first.
+///
+/// Equal items are ranked by index, lowest first.
+#[must_use]
+pub fn top_k_refs<T: Ord>(items: &[T], k: usize) -> Vec<&T> {
+    argtop_k(items, k).into_iter().map(|i| &items[i]).collect()
+}
+
+/// Returns references to the `k` items of `items` with the largest keys, as
+/// computed by `key`, largest first.
+///
+/// Equal keys are ranked by index, lowest first.
+pub fn top_k_refs_by_key<T, K, F>(items: &[T], k: usize, key: F) -> Vec<&T>
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    argtop_k_by_key(items, k, key)
+        .into_iter()
+        .map(|i| &items[i])
+        .collect()
+}
+
+/// Ranks `keys` with a `TopQueue`, returning the positions of the top `k`.
+fn argtop_keys<K: Ord>(keys: impl Iterator<Item = K>, k: usize) -> Vec<usize> {
+    // `Reverse` makes the lower of two equal keys' indices the larger entry.
+    let indexed = keys.enumerate().map(|(i, key)| (key, Reverse(i)));
+    let mut top = TopQueue::from_iter(k, indexed).into_vec();
+    top.reverse();
+    top.into_iter().map(|(_, Reverse(i))| i).collect()
+}
+
+/// Returns the items ranked `offset` to `offset + limit - 1` among all the
+/// items of `iter`, largest first, where the largest item has rank 0.
+///
+/// This keeps at most `offset + limit` items in memory, in a `TopQueue`.
+/// The page is cut short, or empty, if `iter` runs out first. Equal items hold
+/// consecutive ranks, in unspecified order among themselves.
+///
+/// ```
+/// # use topqueue::util::top_page;
+/// let scores = [12, 99, 45, 7, 63, 81, 30];
+/// assert_eq!(vec![63, 45], top_page(scores, 2, 2));
+/// assert_eq!(vec![7], top_page(scores, 6, 2));
+/// ```
+pub fn top_page<I>(iter: I, offset: usize, limit: usize) -> Vec<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Ord,
+{
+    if limit == 0 {
+        return Vec::new();
+    }
+    let q = TopQueue::from_iter(offset.saturating_add(limit), iter);
+    let mut items = q.into_vec();
+    items.reverse();
+    items.split_off(offset.min(items.len()))
+}
+
+/// An iterator that merges already-sorted iterators, created by [`kmerge`].
+pub type KMerge<I> = KMergeBy<I, fn(&<I as Iterator>::Item, &<I as Iterator>::Item) -> Ordering>;
+
+/// Lazily merges iterators that are each sorted in ascending order into a
+/// single ascending iterator.
+///
+/// ```
+/// # use topqueue::util::kmerge;
+/// let merged: Vec<_> = kmerge([vec![1, 4, 7], vec![2, 5], vec![3, 6, 9]]).collect();
+/// assert_eq!(vec![1, 2, 3, 4, 5, 6, 7, 9], merged);
+/// ```
+///
+/// See [`kmerge_by`] for details.
+pub fn kmerge<I>(iters: I) -> KMerge<<I::Item as IntoIterator>::IntoIter>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator,
+    <I::Item as IntoIterator>::Item: Ord,
+{
+    kmerge_by(iters, Ord::cmp)
+}
+
+/// Lazily merges iterators that are each sorted by `cmp` into a single
+/// iterator sorted by `cmp`.
+///
+/// Like `TopQueue`, this keeps a min-heap with one entry per source: the
+/// source's next item. Each item yielded costs *O*(log *k*) for *k* sources,
+/// and a source is only advanced when its previous item is yielded, so taking
+/// the first *n* items never pulls more than *n* + *k* from the sources.
+/// Equal items come out in the order of their sources.
+///
+/// To get the global top 3 of shards that are each sorted largest first:
+///
+/// ```
+/// # use topqueue::util::kmerge_by;
+/// let shards = [vec![90, 70, 10], vec![95, 60], vec![80, 75, 70]];
+/// let top: Vec<_> = kmerge_by(shards, |a, b| b.cmp(a)).take(3).collect();
+/// assert_eq!(vec![95, 90, 80], top);
+/// ```
+pub fn kmerge_by<I, F>(iters: I, cmp: F) -> KMergeBy<<I::Item as IntoIterator>::IntoIter, F>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator,
+    F: FnMut(&<I::Item as IntoIterator>::Item, &<I::Item as IntoIterator>::Item) -> Ordering,
+{
+    let heap = iters
+        .into_iter()
+        .map(IntoIterator::into_iter)
+        .enumerate()
+        .filter_map(|(source, mut tail)| tail.next().map(|head| HeadTail { head, source, tail }))
+        .collect();
+    let mut merge = KMergeBy {
+        heap,
+        cmp,
+        dedup: false,
+    };
+    for i in (0..merge.heap.len() / 2).rev() {
+        merge.sift_down(i);
+    }
+    merge
+}
+
+/// The next item of one source, along with the rest of it.
+struct HeadTail<I: Iterator> {
+    head: I::Item,
+    source: usize,
+    tail: I,
+}
+
+/// An iterator that merges iterators sorted by a comparator, created by
+/// [`kmerge_by`].
+pub struct KMergeBy<I: Iterator, F> {
+    heap: Vec<HeadTail<I>>,
+    cmp: F,
+    dedup: bool,
+}
+
+impl<I, F> KMergeBy<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    /// Skips items equal to the previous one, so each distinct value is
+    /// yielded once. The first of each run of equal items is kept.
+    #[must_use]
+    pub fn dedup(mut self) -> Self {
+        self.dedup = true;
+        self
+    }
+
+    fn less(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (&self.heap[a], &self.heap[b]);
+        (self.cmp)(&a.head, &b.head).then(a.source.cmp(&b.source)) == Ordering::Less
+    }
+
+    fn sift_down(&mut self, mut parent: usize) {
+        loop {
+            let (left, right) = (2 * parent + 1, 2 * parent + 2);
+            let mut least = parent;
+            if left < self.heap.len() && self.less(left, least) {
+                least = left;
+            }
+            if right < self.heap.len() && self.less(right, least) {
+                least = right;
+            }
+            if least == parent {
+                return;
+            }
+            self.heap.swap(parent, least);
+            parent = least;
+        }
+    }
+
+    /// Takes the smallest head, refilling from its source.
+    fn pop_head(&mut self) -> Option<I::Item> {
+        let root = self.heap.first_mut()?;
+        let head = match root.tail.next() {
+            Some(next) => mem::replace(&mut root.head, next),
+            None => self.heap.swap_remove(0).head,
+        };
+        self.sift_down(0);
+        Some(head)
+    }
+}
+
+impl<I, F> Iterator for KMergeBy<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let item = self.pop_head()?;
+        if self.dedup {
+            while let Some(root) = self.heap.first() {
+                if (self.cmp)(&root.head, &item) != Ordering::Equal {
+                    break;
+                }
+                self.pop_head();
+            }
+        }
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (low, high) = self
+            .heap
+            .iter()
+            .fold((0_usize, Some(0_usize)), |(low, high), ht| {
+                let (tail_low, tail_high) = ht.tail.size_hint();
+                let high = high
+                    .zip(tail_high)
+                    .and_then(|(high, tail_high)| high.checked_add(tail_high)?.checked_add(1));
+                (low.saturating_add(tail_low).saturating_add(1), high)
+            });
+        if self.dedup {
+            (low.min(1), high)
+        } else {
+            (low, high)
+        }
+    }
+}
+
+impl<I, F> FusedIterator for KMergeBy<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+}
+
 #[cfg(test)]
 mod tests {
     use std::any::type_name_of_val as type_of;
@@ -80,6 +377,99 @@ mod tests {
         assert_eq!(&[9, 5, 4], &get_top(&[1, 2, 3, 5, 9, 4], 3)[..]);
     }
 
+    #[test]
+    fn test_ord_f64() {
+        let mut nums = [2.5, f64::NAN, -1.0, f64::INFINITY, 0.0].map(OrdF64);
+        nums.sort_unstable();
+        assert_eq!(OrdF64(-1.0), nums[0]);
+        assert!(nums[4].0.is_nan());
+        assert_eq!(OrdF64(0.0), OrdF64(0.0));
+        assert_ne!(OrdF64(0.0), OrdF64(-0.0));
+    }
+
+    #[test]
+    fn test_argtop_k() {
+        let nums: Vec<i32> = rands().take(1000).collect();
+        let top = get_top(&nums, 10);
+        let indices = argtop_k(&nums, 10);
+        assert_eq!(top, indices.iter().map(|&i| nums[i]).collect::<Vec<_>>());
+        assert_eq!(
+            top,
+            top_k_refs(&nums, 10)
+                .into_iter()
+                .copied()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(nums.len(), argtop_k(&nums, 5000).len());
+        assert!(argtop_k(&nums, 0).is_empty());
+    }
+
+    #[test]
+    fn test_argtop_k_ties_and_keys() {
+        assert_eq!(vec![1, 3, 4, 0], argtop_k(&[2, 5, 1, 5, 5, 2], 4));
+
+        let words = ["kiwi", "fig", "banana", "apple", "plum"];
+        assert_eq!(vec![2, 3], argtop_k_by_key(&words, 2, |w| w.len()));
+        // "kiwi" and "plum" tie on length; the lower index wins.
+        assert_eq!(
+            vec![&"banana", &"apple", &"kiwi"],
+            top_k_refs_by_key(&words, 3, |w| w.len())
+        );
+    }
+
+    #[test]
+    fn test_top_page() {
+        let nums: Vec<i32> = rands().take(500).collect();
+        let ranked = get_top(&nums, nums.len());
+        assert_eq!(ranked[..50], top_page(nums.iter().copied(), 0, 50)[..]);
+        assert_eq!(ranked[50..100], top_page(nums.iter().copied(), 50, 50)[..]);
+        assert_eq!(ranked[480..], top_page(nums.iter().copied(), 480, 50)[..]);
+        assert!(top_page(nums.iter().copied(), 500, 50).is_empty());
+        assert!(top_page(nums, 0, 0).is_empty());
+    }
+
+    #[test]
+    fn test_kmerge() {
+        let runs = [vec![1, 3, 3, 8], vec![], vec![2, 3, 9], vec![0]];
+        let merged: Vec<_> = kmerge(runs.clone()).collect();
+        assert_eq!(vec![0, 1, 2, 3, 3, 3, 8, 9], merged);
+
+        let deduped: Vec<_> = kmerge(runs).dedup().collect();
+        assert_eq!(vec![0, 1, 2, 3, 8, 9], deduped);
+
+        assert_eq!(None, kmerge(Vec::<Vec<u8>>::new()).next());
+    }
+
+    #[test]
+    fn test_kmerge_by_is_stable_and_lazy() {
+        use std::cell::Cell;
+
+        // Sorted by the first field only; the second records the source.
+        let runs = [vec![(1, 'a'), (2, 'a')], vec![(1, 'b'), (2, 'b')]];
+        let merged: Vec<_> = kmerge_by(runs, |x, y| x.0.cmp(&y.0)).collect();
+        assert_eq!(vec![(1, 'a'), (1, 'b'), (2, 'a'), (2, 'b')], merged);
+
+        let pulled = Cell::new(0);
+        let shards: Vec<_> = (0..50)
+            .map(|shard| {
+                (0..1000)
+                    .rev()
+                    .map(move |n| n * 50 + shard)
+                    .inspect(|_| pulled.set(pulled.get() + 1))
+            })
+            .collect();
+        let top: Vec<_> = kmerge_by(shards, |a, b| b.cmp(a)).take(100).collect();
+        assert_eq!(get_top(&(0..50_000).collect::<Vec<_>>(), 100), top);
+        assert!(pulled.get() <= 100 + 50);
+    }
+
+    #[test]
+    fn test_kmerge_size_hint() {
+        let merge = kmerge([vec![1, 2], vec![3], vec![]]);
+        assert_eq!((3, Some(3)), merge.size_hint());
+        assert_eq!((1, Some(3)), merge.dedup().size_hint());
+    }
+
     #[test]
     fn test_with_type() {
         assert_eq!("i32", type_of(&5_i32));
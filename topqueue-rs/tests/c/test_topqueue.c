@@ -0,0 +1,76 @@
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "topqueue.h"
+
+static int failures = 0;
+
+#define CHECK(cond)                                                   \
+    do {                                                              \
+        if (!(cond)) {                                                \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,    \
+                    __LINE__, #cond);                                 \
+            failures++;                                               \
+        }                                                             \
+    } while (0)
+
+static void test_i64(void) {
+    TopQueueI64 *q = topqueue_i64_new(3);
+    CHECK(q != NULL);
+
+    for (int64_t n = 0; n < 10; n++) {
+        CHECK(topqueue_i64_push(q, n) == 0);
+    }
+    CHECK(topqueue_i64_len(q) == 3);
+
+    int64_t out[3];
+    CHECK(topqueue_i64_drain_sorted(q, out, 2) == TOPQUEUE_ERROR);
+    CHECK(topqueue_i64_drain_sorted(q, out, 3) == 3);
+    CHECK(out[0] == 7 && out[1] == 8 && out[2] == 9);
+    CHECK(topqueue_i64_len(q) == 0);
+
+    topqueue_i64_free(&q);
+    CHECK(q == NULL);
+    topqueue_i64_free(&q);
+}
+
+static void test_f64(void) {
+    TopQueueF64 *q = topqueue_f64_new(2);
+    double nums[] = {0.5, 2.25, -1.0, 1.5};
+
+    for (size_t i = 0; i < sizeof(nums) / sizeof(nums[0]); i++) {
+        CHECK(topqueue_f64_push(q, nums[i]) == 0);
+    }
+
+    double out[4];
+    CHECK(topqueue_f64_drain_sorted(q, out, 4) == 2);
+    CHECK(out[0] == 1.5 && out[1] == 2.25);
+
+    topqueue_f64_free(&q);
+    topqueue_f64_free(&q);
+}
+
+static void test_null(void) {
+    CHECK(topqueue_i64_push(NULL, 1) == -1);
+    CHECK(topqueue_i64_len(NULL) == 0);
+    CHECK(topqueue_i64_drain_sorted(NULL, NULL, 0) == TOPQUEUE_ERROR);
+    topqueue_i64_free(NULL);
+
+    CHECK(topqueue_f64_push(NULL, 1.0) == -1);
+    CHECK(topqueue_f64_len(NULL) == 0);
+    CHECK(topqueue_f64_drain_sorted(NULL, NULL, 0) == TOPQUEUE_ERROR);
+    topqueue_f64_free(NULL);
+}
+
+int main(void) {
+    test_i64();
+    test_f64();
+    test_null();
+
+    if (failures) {
+        fprintf(stderr, "%d check(s) failed\n", failures);
+        return EXIT_FAILURE;
+    }
+    puts("ok");
+    return EXIT_SUCCESS;
+}
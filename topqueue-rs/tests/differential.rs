@@ -0,0 +1,196 @@
+//! Runs random and adversarial inputs through every top-k implementation in
+//! the crate, and checks they all keep the same items.
+//!
+//! When proptest finds a failing input, it shrinks it and saves its seed to
+//! `tests/differential.proptest-regressions`, which is checked in so that
+//! every later run tries those inputs first.
+
+use std::cmp::Reverse;
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::select;
+use proptest::test_runner::FileFailurePersistence;
+
+use topqueue::extremes::ExtremesQueue;
+use topqueue::heap::{DaryHeap, MinMaxHeap};
+use topqueue::topqueue_basic;
+use topqueue::topqueue_final::TopQueue;
+use topqueue::util::get_top;
+
+/// The largest `k` of `nums` by each implementation, each sorted ascending.
+fn all_top_k(nums: &[i32], k: usize) -> Vec<(&'static str, Vec<i32>)> {
+    let feed = |mut q: TopQueue<i32>| {
+        nums.iter().for_each(|&n| q.push(n));
+        q
+    };
+    let mut basic = topqueue_basic::TopQueue::new(k);
+    nums.iter().for_each(|&n| basic.push(n));
+    let mut batched = TopQueue::new(k);
+    batched.push_batch(nums);
+    let mut dary = TopQueue::new(k).with_heap::<DaryHeap<_, 4>>();
+    nums.iter().for_each(|&n| dary.push(n));
+    let mut minmax = TopQueue::new(k).with_heap::<MinMaxHeap<_>>();
+    nums.iter().for_each(|&n| minmax.push(n));
+    let mut extremes = ExtremesQueue::new(k);
+    nums.iter().for_each(|&n| extremes.push(n));
+    let mut bottom = TopQueue::new(k);
+    nums.iter()
+        .for_each(|&n| bottom.push(Reverse(-i64::from(n))));
+
+    let mut results = vec![
+        ("get_top", get_top(nums, k)),
+        ("topqueue_basic", basic.into_vec()),
+        ("topqueue_final", feed(TopQueue::new(k)).into_vec()),
+        (
+            "from_iter",
+            TopQueue::from_iter(k, nums.iter().copied()).into_vec(),
+        ),
+        ("push_batch", batched.into_vec()),
+        (
+            "drain_sorted",
+            feed(TopQueue::new(k)).drain_sorted().collect(),
+        ),
+        ("dary_heap", dary.into_vec()),
+        ("minmax_heap", minmax.into_vec()),
+        (
+            "weighted_by_count",
+            feed(TopQueue::with_weigher(k, usize::MAX, |_| 1)).into_vec(),
+        ),
+        (
+            "weighted_by_budget",
+            feed(TopQueue::with_weigher(usize::MAX, k, |_| 1)).into_vec(),
+        ),
+        ("extremes", extremes.top().into_iter().copied().collect()),
+        (
+            "reversed_bottom",
+            bottom
+                .into_vec()
+                .into_iter()
+                .map(|r| i32::try_from(-r.0).unwrap())
+                .collect(),
+        ),
+    ];
+    for (_, items) in &mut results {
+        items.sort_unstable();
+    }
+    results
+}
+
+fn assert_all_agree(nums: &[i32], k: usize) -> Result<(), TestCaseError> {
+    let mut expected = nums.to_vec();
+    expected.sort_unstable();
+    let expected = expected.split_off(nums.len().saturating_sub(k));
+    for (name, items) in all_top_k(nums, k) {
+        prop_assert_eq!(&expected, &items, "{} with k = {}", name, k);
+    }
+    Ok(())
+}
+
+/// Values that are mostly random, or drawn from a few to force duplicates,
+/// or the extremes of `i32`.
+fn values() -> impl Strategy<Value = i32> {
+    prop_oneof![
+        any::<i32>(),
+        0..4,
+        select(vec![
+            i32::MIN,
+            i32::MIN + 1,
+            -1,
+            0,
+            1,
+            i32::MAX - 1,
+            i32::MAX
+        ]),
+    ]
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Order {
+    AsGenerated,
+    Ascending,
+    Descending,
+    /// Alternately the smallest and largest left, so the queue's minimum
+    /// keeps changing.
+    Zigzag,
+}
+
+fn arrange(mut nums: Vec<i32>, order: Order) -> Vec<i32> {
+    match order {
+        Order::AsGenerated => {}
+        Order::Ascending => nums.sort_unstable(),
+        Order::Descending => nums.sort_unstable_by(|a, b| b.cmp(a)),
+        Order::Zigzag => {
+            nums.sort_unstable();
+            let (low, high) = nums.split_at(nums.len() / 2);
+            let mut zigzag = Vec::with_capacity(nums.len());
+            let mut high = high.iter().rev();
+            for &n in low {
+                zigzag.push(n);
+                zigzag.extend(high.next());
+            }
+            zigzag.extend(high);
+            nums = zigzag;
+        }
+    }
+    nums
+}
+
+/// An input, and a capacity that's 0, 1, up to its length, or beyond it, as
+/// far as `usize::MAX`.
+fn inputs() -> impl Strategy<Value = (Vec<i32>, usize)> {
+    let order = prop_oneof![
+        Just(Order::AsGenerated),
+        Just(Order::Ascending),
+        Just(Order::Descending),
+        Just(Order::Zigzag),
+    ];
+    (vec(values(), 0..300), order).prop_flat_map(|(nums, order)| {
+        let n = nums.len();
+        let k = prop_oneof![
+            Just(0),
+            Just(1),
+            0..=n,
+            Just(n + 1),
+            n..n + 1000,
+            Just(usize::MAX),
+        ];
+        (Just(arrange(nums, order)), k)
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 1000,
+        failure_persistence: Some(Box::new(FileFailurePersistence::WithSource("proptest-regressions"))),
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn implementations_agree((nums, k) in inputs()) {
+        assert_all_agree(&nums, k)?;
+    }
+
+    #[test]
+    fn implementations_agree_on_duplicates(
+        nums in vec(select(vec![-1, 0, 1]), 0..100),
+        k in 0_usize..8,
+    ) {
+        assert_all_agree(&nums, k)?;
+    }
+}
+
+#[test]
+fn implementations_agree_on_fixed_cases() {
+    let cases: [(&[i32], usize); 6] = [
+        (&[], 0),
+        (&[], 3),
+        (&[7], 0),
+        (&[i32::MIN, i32::MAX, i32::MIN], 1),
+        (&[2, 2, 2, 1, 2], 2),
+        (&[5, 1, 9, 3, 7, 2], 6),
+    ];
+    for (nums, k) in cases {
+        assert_all_agree(nums, k).unwrap();
+    }
+}
@@ -0,0 +1,60 @@
+//! Builds the C test program in `tests/c` against the `cdylib` and runs it.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Builds the library in its own target directory, so as not to contend with
+/// the cargo process running this test, and returns the output directory.
+fn build_cdylib(scratch: &Path) -> PathBuf {
+    let target_dir = scratch.join("target");
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--lib", "--quiet", "--manifest-path"])
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status()
+        .expect("failed to run cargo");
+    assert!(status.success(), "cargo build failed: {status}");
+    target_dir.join("debug")
+}
+
+#[test]
+fn c_test_program_passes() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+    let lib_dir = build_cdylib(&scratch);
+    let exe = scratch.join("test_topqueue");
+
+    let status = Command::new(env::var("CC").unwrap_or_else(|_| "cc".into()))
+        .args([
+            "-std=c99",
+            "-Werror",
+            "-Wall",
+            "-Wextra",
+            "-Wconversion",
+            "-pedantic",
+        ])
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/c/test_topqueue.c"))
+        .arg("-o")
+        .arg(&exe)
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-ltopqueue")
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "C compilation failed: {status}");
+
+    let output = Command::new(&exe)
+        .output()
+        .expect("failed to run test program");
+    assert!(
+        output.status.success(),
+        "C test program failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!("ok\n", String::from_utf8_lossy(&output.stdout));
+}
@@ -0,0 +1,73 @@
+//! Records traces to files and runs `topqueue-replay` over them.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use topqueue::topqueue_final::TopQueue;
+use topqueue::trace::Recorder;
+use topqueue::util::OrdF64;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn replay(args: &[&Path]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_topqueue-replay"))
+        .args(args)
+        .output()
+        .expect("failed to run topqueue-replay")
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap().trim_end()
+}
+
+#[test]
+fn replays_and_bisects_recorded_files() {
+    let dir = scratch_dir("replay");
+    let path = dir.join("floats.trace");
+    let out = BufWriter::new(File::create(&path).unwrap());
+    let mut q = TopQueue::new(3).with_observer(Recorder::to_writer(out, 3));
+    for n in [0.5, -1.0, 2.25, 8.0, 3.5] {
+        q.push(OrdF64(n));
+    }
+    q.record_snapshot();
+    q.set_capacity(2);
+    q.into_observer().finish().unwrap();
+
+    let output = replay(&[&path]);
+    assert!(output.status.success());
+    assert_eq!(
+        "7 ops replayed: [OrdF64(8.0), OrdF64(3.5)]",
+        stdout(&output)
+    );
+    let output = replay(&[&path, Path::new("--bisect")]);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("7 ops agree with the oracle"));
+
+    // A weighted queue keeps less than its replay does.
+    let path = dir.join("weighted.trace");
+    let out = BufWriter::new(File::create(&path).unwrap());
+    let mut q = TopQueue::with_weigher(4, 3, |&n: &i32| n.unsigned_abs() as usize)
+        .with_observer(Recorder::to_writer(out, 4));
+    for n in [1, 2, 3] {
+        q.push(n);
+    }
+    q.record_snapshot();
+    q.into_observer().finish().unwrap();
+
+    let output = replay(&[&path]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "snapshot at op 3 differs: recorded [3], replayed [1, 2, 3]",
+        stdout(&output)
+    );
+
+    let output = replay(&[&dir.join("missing.trace")]);
+    assert_eq!(Some(2), output.status.code());
+}
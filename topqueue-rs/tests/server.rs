@@ -0,0 +1,139 @@
+//! Runs `topqueue-server` and talks to it as a local client would.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+
+/// A running server, killed when dropped.
+struct Server {
+    child: Child,
+    addr: String,
+}
+
+impl Server {
+    fn start(args: &[&str]) -> Server {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_topqueue-server"))
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to start server");
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let addr = line
+            .trim()
+            .strip_prefix("listening on ")
+            .unwrap_or_else(|| panic!("unexpected announcement {line:?}"))
+            .to_string();
+        Server { child, addr }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A client connection, sending one command at a time.
+struct Client<S> {
+    reader: BufReader<S>,
+    writer: S,
+}
+
+impl<S: Read + Write> Client<S> {
+    fn send(&mut self, command: &str) -> String {
+        writeln!(self.writer, "{command}").unwrap();
+        let mut reply = String::new();
+        self.reader.read_line(&mut reply).unwrap();
+        reply.trim_end().to_string()
+    }
+}
+
+fn tcp_client(server: &Server) -> Client<TcpStream> {
+    let stream = TcpStream::connect(&server.addr).unwrap();
+    Client {
+        reader: BufReader::new(stream.try_clone().unwrap()),
+        writer: stream,
+    }
+}
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn tcp_clients_share_queues_and_checkpoints_restore() {
+    let dir = scratch_dir("server-tcp");
+    let dir_arg = dir.to_str().unwrap();
+    let server = Server::start(&["--tcp", "127.0.0.1:0", "--checkpoint-dir", dir_arg]);
+
+    let mut admin = tcp_client(&server);
+    assert_eq!("OK", admin.send("CREATE board 5"));
+    assert_eq!("OK", admin.send("CREATE extra 2"));
+    assert!(admin.send("CREATE board 5").starts_with("ERR"));
+
+    // Four clients push 0..400 between them, concurrently.
+    let feeders: Vec<_> = (0..4)
+        .map(|i| {
+            let mut client = tcp_client(&server);
+            thread::spawn(move || {
+                for n in (i..400).step_by(4) {
+                    assert_eq!("OK", client.send(&format!("PUSH board {n}")));
+                }
+            })
+        })
+        .collect();
+    for feeder in feeders {
+        feeder.join().unwrap();
+    }
+    assert_eq!("OK 399 398 397 396 395", admin.send("TOP board"));
+
+    admin.send("PUSH extra 1000");
+    assert_eq!("OK", admin.send("MERGE board extra"));
+    assert_eq!("OK", admin.send("SNAPSHOT board board.checkpoint"));
+    assert!(admin
+        .send("SNAPSHOT board ../board.checkpoint")
+        .starts_with("ERR"));
+    drop(server);
+
+    let checkpoint = dir.join("board.checkpoint");
+    let restored = Server::start(&[
+        "--tcp",
+        "127.0.0.1:0",
+        "--restore",
+        checkpoint.to_str().unwrap(),
+    ]);
+    let mut client = tcp_client(&restored);
+    assert_eq!("OK 1000 399 398 397 396", client.send("TOP board"));
+    assert_eq!("ERR no queue extra", client.send("TOP extra"));
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket_clients() {
+    let socket = scratch_dir("server-unix").join("topqueue.sock");
+    let server = Server::start(&["--unix", socket.to_str().unwrap()]);
+    assert_eq!(socket.to_str().unwrap(), server.addr);
+
+    let stream = UnixStream::connect(&socket).unwrap();
+    let mut client = Client {
+        reader: BufReader::new(stream.try_clone().unwrap()),
+        writer: stream,
+    };
+    assert_eq!("OK", client.send("CREATE q 2"));
+    for n in [4, -7, 12] {
+        client.send(&format!("PUSH q {n}"));
+    }
+    assert_eq!("OK 12 4", client.send("TOP q"));
+    assert!(client.send("SNAPSHOT q q.checkpoint").starts_with("ERR"));
+}